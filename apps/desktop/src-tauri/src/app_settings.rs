@@ -0,0 +1,47 @@
+//! Settings types registered with the [`shipkit_core::SettingsManager`].
+
+use serde::{Deserialize, Serialize};
+use shipkit_core::Settings;
+
+/// Sample preferences for the Settings panel.
+///
+/// The panel renders a form for this namespace from its JSON Schema.
+#[derive(Debug, Clone, Serialize, Deserialize, Settings)]
+#[settings(namespace = "demo")]
+pub struct DemoSettings {
+    /// Shown at the top of the window.
+    #[settings(default = "Welcome to ShipKit", non_empty)]
+    pub greeting: String,
+    /// Multiplier applied to every font size.
+    #[settings(default = 1.0, min = 0.5, max = 3.0)]
+    pub font_scale: f64,
+    /// Spacing between list items.
+    #[settings(default = "comfortable", one_of = ["comfortable", "compact"])]
+    pub density: String,
+    /// Highlight color as a hex code.
+    #[settings(default = "#3b82f6", pattern = "^#[0-9a-fA-F]{6}$")]
+    pub accent_color: String,
+    /// Show tips on startup.
+    #[settings(default = true)]
+    pub show_tips: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use shipkit_core::settings::typescript::{bindings, write_or_check};
+
+    use super::*;
+
+    /// `src/lib/settings.generated.ts` matches the types above. Regenerate it
+    /// with `SHIPKIT_UPDATE_BINDINGS=1 cargo test`.
+    #[test]
+    fn settings_bindings_are_current() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../src/lib/settings.generated.ts"
+        );
+        let update = std::env::var_os("SHIPKIT_UPDATE_BINDINGS").is_some();
+        write_or_check(path, &bindings(&[DemoSettings::typescript()]), update)
+            .expect("settings bindings");
+    }
+}
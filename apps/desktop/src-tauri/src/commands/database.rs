@@ -1,4 +1,5 @@
-use shipkit_core::MigrationStatus;
+use shipkit_core::db::SeedStatus;
+use shipkit_core::{MigrationEvent, MigrationStatus};
 use tauri::State;
 
 use crate::state::AppState;
@@ -22,3 +23,25 @@ pub fn rollback_migration(
     let mut engine = state.migrations.lock().map_err(|e| e.to_string())?;
     engine.rollback_last().map_err(|e| e.to_string())
 }
+
+#[tauri::command]
+pub fn migration_history(state: State<'_, AppState>) -> Result<Vec<MigrationEvent>, String> {
+    let engine = state.migrations.lock().map_err(|e| e.to_string())?;
+    engine.history().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn seed_status(state: State<'_, AppState>) -> Result<Vec<SeedStatus>, String> {
+    let Some(seeder) = &state.seeder else {
+        return Ok(Vec::new());
+    };
+    seeder.status().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn reseed(state: State<'_, AppState>) -> Result<Vec<SeedStatus>, String> {
+    let Some(seeder) = &state.seeder else {
+        return Ok(Vec::new());
+    };
+    seeder.reseed().map_err(|e| e.to_string())
+}
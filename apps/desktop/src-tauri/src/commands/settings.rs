@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use serde_json::Value;
-use shipkit_core::SettingsBackend;
+use shipkit_core::SettingsManager;
 use tauri::State;
 
 use crate::state::AppState;
@@ -13,8 +13,8 @@ pub fn get_setting(
     key: String,
 ) -> Result<Option<Value>, String> {
     state
-        .settings_store
-        .get(&namespace, &key)
+        .settings
+        .get_value(&namespace, &key)
         .map_err(|e| e.to_string())
 }
 
@@ -26,8 +26,8 @@ pub fn set_setting(
     value: Value,
 ) -> Result<(), String> {
     state
-        .settings_store
-        .set(&namespace, &key, value)
+        .settings
+        .set_value(&namespace, &key, value)
         .map_err(|e| e.to_string())
 }
 
@@ -37,7 +37,7 @@ pub fn get_all_settings(
     namespace: String,
 ) -> Result<HashMap<String, Value>, String> {
     state
-        .settings_store
+        .settings
         .get_all(&namespace)
         .map_err(|e| e.to_string())
 }
@@ -48,7 +48,7 @@ pub fn load_settings(
     namespace: String,
 ) -> Result<Value, String> {
     let all = state
-        .settings_store
+        .settings
         .get_all(&namespace)
         .map_err(|e| e.to_string())?;
     Ok(Value::Object(all.into_iter().collect()))
@@ -60,15 +60,77 @@ pub fn save_settings(
     namespace: String,
     settings: Value,
 ) -> Result<(), String> {
-    if let Value::Object(map) = settings {
-        for (key, val) in map {
-            state
-                .settings_store
-                .set(&namespace, &key, val)
-                .map_err(|e| e.to_string())?;
-        }
-        Ok(())
-    } else {
-        Err("settings must be a JSON object".into())
+    save_all(&state.settings, &namespace, settings)
+}
+
+/// Write every field of `settings`, or none if any of them is invalid.
+fn save_all(manager: &SettingsManager, namespace: &str, settings: Value) -> Result<(), String> {
+    let Value::Object(map) = settings else {
+        return Err("settings must be a JSON object".into());
+    };
+    for (key, val) in &map {
+        manager
+            .validate_value(namespace, key, val)
+            .map_err(|e| e.to_string())?;
+    }
+    for (key, val) in map {
+        manager
+            .set_value(namespace, &key, val)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings_schema(
+    state: State<'_, AppState>,
+    namespace: String,
+) -> Result<Value, String> {
+    state
+        .settings
+        .schema(&namespace)
+        .cloned()
+        .ok_or_else(|| format!("no settings type registered for namespace '{namespace}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use shipkit_core::{ConnectionPool, SqliteSettingsStore};
+
+    use super::*;
+    use crate::app_settings::DemoSettings;
+
+    fn manager() -> SettingsManager {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        let mut manager = SettingsManager::new(SqliteSettingsStore::new(pool).expect("store"));
+        manager.register::<DemoSettings>();
+        manager
+    }
+
+    /// `set_setting` writes through `set_value`, which checks registered
+    /// namespaces.
+    #[test]
+    fn set_setting_rejects_out_of_range_values() {
+        let manager = manager();
+        let err = manager
+            .set_value("demo", "font_scale", json!(10.0))
+            .expect_err("out of range");
+        assert!(err.to_string().contains("must be at most 3"));
+        assert_eq!(manager.get_value("demo", "font_scale").expect("get"), None);
+    }
+
+    #[test]
+    fn save_settings_writes_nothing_if_a_field_is_invalid() {
+        let manager = manager();
+        let settings = json!({ "density": "compact", "font_scale": 10.0 });
+        assert!(save_all(&manager, "demo", settings).is_err());
+        assert_eq!(manager.get_value("demo", "density").expect("get"), None);
+
+        save_all(&manager, "demo", json!({ "density": "compact" })).expect("valid");
+        assert_eq!(
+            manager.get_value("demo", "density").expect("get"),
+            Some(json!("compact"))
+        );
     }
 }
@@ -1,4 +1,4 @@
-use shipkit_core::{SettingsBackend, ThemeDefinition};
+use shipkit_core::ThemeDefinition;
 use tauri::State;
 
 use crate::state::AppState;
@@ -17,8 +17,8 @@ pub fn set_theme(state: State<'_, AppState>, name: String) -> Result<ThemeDefini
 
     // Persist theme selection
     state
-        .settings_store
-        .set(
+        .settings
+        .set_value(
             "shipkit_internal",
             "active_theme",
             serde_json::json!(name),
@@ -1,14 +1,17 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod app_settings;
 mod commands;
 mod state;
 
 use std::sync::{Mutex, RwLock};
 
+use tauri::Manager;
+
 use shipkit_core::theme::default_themes;
 use shipkit_core::{
-    ConnectionPool, LoggerConfig, Migration, MigrationEngine, SettingsBackend,
-    SqliteSettingsStore, ThemeEngine,
+    ConnectionPool, LoggerConfig, Migration, MigrationEngine, SeedEnvironment, Seeder,
+    SettingsManager, SqliteSettingsStore, ThemeEngine,
 };
 
 #[allow(clippy::expect_used)]
@@ -34,12 +37,12 @@ fn main() {
     let pool =
         ConnectionPool::new(data_dir.join("data.db")).expect("failed to create connection pool");
 
-    // 3. Settings store
-    let settings_store =
-        SqliteSettingsStore::new(pool.clone()).expect("failed to create settings store");
-
-    // 4. Migration engine with a demo migration
+    // 3. Migration engine with a demo migration, backed up before each upgrade.
+    //    Pending migrations are applied once the window exists (see `setup`).
     let mut migration_engine = MigrationEngine::new(pool.clone());
+    migration_engine
+        .set_backup_dir(data_dir.join("backups"))
+        .set_app_version(env!("CARGO_PKG_VERSION"));
     migration_engine.register(Migration {
         version: 1,
         name: "create_notes".into(),
@@ -47,10 +50,40 @@ fn main() {
         down_sql: Some("DROP TABLE IF EXISTS notes;".into()),
     });
 
-    // 5. Theme engine — restore persisted theme preference
+    // 4. Settings store, whose table is migrated now by the engine above, and
+    //    wrapped so writes can be broadcast to every window
+    let mut settings = SettingsManager::new(
+        SqliteSettingsStore::with_engine(pool.clone(), &mut migration_engine)
+            .expect("failed to create settings store"),
+    );
+    settings.register::<app_settings::DemoSettings>();
+
+    // 5. Seed data — sample notes for dev and demo builds, run after migrations.
+    //    Debug builds seed `dev` unless SHIPKIT_ENV=dev|test|demo picks another
+    //    environment; release builds only seed when SHIPKIT_ENV is set, so
+    //    shipped builds never write sample data into a user's database.
+    let seed_environment = std::env::var("SHIPKIT_ENV")
+        .ok()
+        .and_then(|env| env.parse().ok())
+        .or(cfg!(debug_assertions).then_some(SeedEnvironment::Dev));
+    let seeder = seed_environment.map(|environment| {
+        let mut seeder = Seeder::new(pool.clone(), environment);
+        seeder.register_sql(
+            "demo_notes",
+            &[SeedEnvironment::Dev, SeedEnvironment::Demo],
+            "DELETE FROM notes WHERE title LIKE 'Demo: %';
+             INSERT INTO notes (title, content) VALUES
+                 ('Demo: Welcome to ShipKit', 'This note was created by the demo_notes seed.'),
+                 ('Demo: Migrations', 'Open the Database panel to see applied migrations and their history.'),
+                 ('Demo: Re-seeding', 'Use Re-seed to restore these notes after editing them.');",
+        );
+        seeder
+    });
+
+    // 6. Theme engine — restore persisted theme preference
     let themes = default_themes();
-    let active_theme = settings_store
-        .get("shipkit_internal", "active_theme")
+    let active_theme = settings
+        .get_value("shipkit_internal", "active_theme")
         .ok()
         .flatten()
         .and_then(|v| v.as_str().map(String::from))
@@ -66,22 +99,53 @@ fn main() {
     let app_state = state::AppState {
         _pool: pool,
         migrations: Mutex::new(migration_engine),
-        settings_store,
+        seeder,
+        settings,
         theme_engine: RwLock::new(theme_engine),
         logger,
     };
 
     tauri::Builder::default()
         .manage(app_state)
+        .setup(|app| {
+            app.state::<state::AppState>().settings.on_change(
+                shipkit_core::settings::changes::emit_to(app.handle().clone()),
+            );
+
+            // Migrate off the main thread so the window can show progress events
+            let handle = app.handle().clone();
+            std::thread::spawn(move || {
+                let state = handle.state::<state::AppState>();
+                let Ok(mut engine) = state.migrations.lock() else {
+                    return;
+                };
+                engine.set_progress_observer(shipkit_core::db::progress::emit_to(handle.clone()));
+                // A failed upgrade restores the backup; keep running so the Database panel can show it
+                if let Err(e) = engine.apply_pending() {
+                    tracing::error!(error = %e, "failed to apply migrations at startup");
+                    return;
+                }
+                if let Some(seeder) = &state.seeder
+                    && let Err(e) = seeder.run()
+                {
+                    tracing::error!(error = %e, "failed to run seeds at startup");
+                }
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::database::migration_status,
             commands::database::apply_migrations,
             commands::database::rollback_migration,
+            commands::database::migration_history,
+            commands::database::seed_status,
+            commands::database::reseed,
             commands::settings::get_setting,
             commands::settings::set_setting,
             commands::settings::get_all_settings,
             commands::settings::load_settings,
             commands::settings::save_settings,
+            commands::settings::get_settings_schema,
             commands::theme::get_theme,
             commands::theme::set_theme,
             commands::theme::list_themes,
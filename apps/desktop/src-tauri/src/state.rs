@@ -1,13 +1,17 @@
 use std::sync::{Mutex, RwLock};
 
-use shipkit_core::{ConnectionPool, Logger, MigrationEngine, SqliteSettingsStore, ThemeEngine};
+use shipkit_core::{ConnectionPool, Logger, MigrationEngine, Seeder, SettingsManager, ThemeEngine};
 
 /// All application state managed by Tauri.
 pub struct AppState {
-    /// Kept alive so the pool isn't dropped. Commands access it via settings_store/migrations.
+    /// Kept alive so the pool isn't dropped. Commands access it via settings/migrations.
     pub _pool: ConnectionPool,
     pub migrations: Mutex<MigrationEngine>,
-    pub settings_store: SqliteSettingsStore,
+    /// `None` when no seed environment is selected (release builds without
+    /// `SHIPKIT_ENV`).
+    pub seeder: Option<Seeder>,
+    /// Every write goes through the manager so windows hear about it.
+    pub settings: SettingsManager,
     pub theme_engine: RwLock<ThemeEngine>,
     pub logger: Logger,
 }
@@ -1,39 +1,88 @@
 import { useEffect, useState } from "react";
-import type { MigrationStatus } from "../lib/bindings";
+import type {
+  MigrationEvent,
+  MigrationProgress,
+  MigrationStatus,
+  SeedStatus,
+} from "../lib/bindings";
 import {
   migrationStatus,
+  migrationHistory,
   applyMigrations,
   rollbackMigration,
+  onMigrationProgress,
+  seedStatus,
+  reseed,
 } from "../lib/invoke";
 
 export function DatabasePanel() {
   const [migrations, setMigrations] = useState<MigrationStatus[]>([]);
+  const [history, setHistory] = useState<MigrationEvent[]>([]);
+  const [seeds, setSeeds] = useState<SeedStatus[]>([]);
   const [error, setError] = useState<string | null>(null);
+  const [progress, setProgress] = useState<MigrationProgress | null>(null);
 
   const refresh = () => {
     migrationStatus()
       .then(setMigrations)
       .catch((e: unknown) => setError(String(e)));
+    migrationHistory()
+      .then(setHistory)
+      .catch((e: unknown) => setError(String(e)));
+    seedStatus()
+      .then(setSeeds)
+      .catch((e: unknown) => setError(String(e)));
   };
 
   useEffect(refresh, []);
 
+  useEffect(() => {
+    const unlisten = onMigrationProgress((p) => {
+      setProgress(p);
+      if (p.event !== "started") refresh();
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
   const handleApply = () => {
     applyMigrations()
-      .then(setMigrations)
+      .then(() => refresh())
+      .catch((e: unknown) => {
+        setError(String(e));
+        refresh();
+      });
+  };
+
+  const handleReseed = () => {
+    reseed()
+      .then(setSeeds)
       .catch((e: unknown) => setError(String(e)));
   };
 
   const handleRollback = () => {
     rollbackMigration()
       .then(() => refresh())
-      .catch((e: unknown) => setError(String(e)));
+      .catch((e: unknown) => {
+        setError(String(e));
+        refresh();
+      });
   };
 
   return (
     <div style={{ border: "1px solid #ccc", borderRadius: 8, padding: 16 }}>
       <h2>Database Migrations</h2>
       {error && <p style={{ color: "red" }}>{error}</p>}
+      {progress && progress.event === "started" && (
+        <div style={{ marginBottom: 12 }}>
+          <p>
+            Migrating {progress.index} of {progress.total}: {progress.set}/
+            {progress.name}
+          </p>
+          <progress value={progress.index - 1} max={progress.total} />
+        </div>
+      )}
       <div style={{ display: "flex", gap: 8, marginBottom: 12 }}>
         <button onClick={handleApply}>Apply All</button>
         <button onClick={handleRollback}>Rollback Last</button>
@@ -45,6 +94,7 @@ export function DatabasePanel() {
         <table style={{ width: "100%", borderCollapse: "collapse" }}>
           <thead>
             <tr>
+              <th style={{ textAlign: "left" }}>Set</th>
               <th style={{ textAlign: "left" }}>Version</th>
               <th style={{ textAlign: "left" }}>Name</th>
               <th style={{ textAlign: "left" }}>Status</th>
@@ -53,7 +103,8 @@ export function DatabasePanel() {
           </thead>
           <tbody>
             {migrations.map((m) => (
-              <tr key={m.version}>
+              <tr key={`${m.set}:${m.version}`}>
+                <td>{m.set}</td>
                 <td>{m.version}</td>
                 <td>{m.name}</td>
                 <td>{m.applied ? "Applied" : "Pending"}</td>
@@ -63,6 +114,54 @@ export function DatabasePanel() {
           </tbody>
         </table>
       )}
+      <h3>Seeds</h3>
+      {seeds.length === 0 ? (
+        <p>No seeds for this environment.</p>
+      ) : (
+        <>
+          <ul>
+            {seeds.map((s) => (
+              <li key={s.name}>
+                {s.name}: {s.ran ? `ran ${s.ran_at}` : "not run"}
+              </li>
+            ))}
+          </ul>
+          <button onClick={handleReseed}>Re-seed</button>
+        </>
+      )}
+      <h3>History</h3>
+      {history.length === 0 ? (
+        <p>No migrations have run yet.</p>
+      ) : (
+        <table style={{ width: "100%", borderCollapse: "collapse" }}>
+          <thead>
+            <tr>
+              <th style={{ textAlign: "left" }}>When</th>
+              <th style={{ textAlign: "left" }}>Event</th>
+              <th style={{ textAlign: "left" }}>Version</th>
+              <th style={{ textAlign: "left" }}>Duration</th>
+              <th style={{ textAlign: "left" }}>App Version</th>
+              <th style={{ textAlign: "left" }}>Result</th>
+            </tr>
+          </thead>
+          <tbody>
+            {[...history].reverse().map((e) => (
+              <tr key={e.id}>
+                <td>{e.occurred_at}</td>
+                <td>{e.kind}</td>
+                <td>
+                  {e.set}/{e.version} ({e.name})
+                </td>
+                <td>{e.duration_ms} ms</td>
+                <td>{e.app_version ?? "-"}</td>
+                <td style={{ color: e.success ? undefined : "red" }}>
+                  {e.success ? "OK" : e.error}
+                </td>
+              </tr>
+            ))}
+          </tbody>
+        </table>
+      )}
     </div>
   );
 }
@@ -0,0 +1,126 @@
+import { useEffect, useState } from "react";
+import type { SettingsProperty, SettingsSchema } from "../lib/bindings";
+import { getSettingsSchema, loadSettings, setSetting } from "../lib/invoke";
+
+// Non-null JSON type of a property, if it declares one.
+function typeOf(property: SettingsProperty): string | undefined {
+  return Array.isArray(property.type)
+    ? property.type.find((t) => t !== "null")
+    : property.type;
+}
+
+// Form generated from a namespace's JSON Schema. Renders nothing when no
+// settings type is registered for the namespace.
+export function SettingsForm({ namespace }: { namespace: string }) {
+  const [schema, setSchema] = useState<SettingsSchema | null>(null);
+  const [values, setValues] = useState<Record<string, unknown>>({});
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    setSchema(null);
+    setValues({});
+    setError(null);
+    getSettingsSchema(namespace)
+      .then((s) => {
+        setSchema(s);
+        loadSettings(namespace)
+          .then(setValues)
+          .catch((e: unknown) => setError(String(e)));
+      })
+      .catch(() => setSchema(null));
+  }, [namespace]);
+
+  if (!schema) return null;
+
+  const save = (key: string, value: unknown) => {
+    setSetting(namespace, key, value)
+      .then(() => {
+        setValues((current) => ({ ...current, [key]: value }));
+        setError(null);
+      })
+      .catch((e: unknown) => setError(String(e)));
+  };
+
+  const input = (key: string, property: SettingsProperty) => {
+    const current = key in values ? values[key] : property.default;
+    // Remount uncontrolled inputs when the stored value changes
+    const inputKey = `${namespace}.${key}.${JSON.stringify(current)}`;
+    const type = typeOf(property);
+
+    if (property.enum) {
+      const options = property.enum;
+      return (
+        <select
+          value={String(options.indexOf(current))}
+          onChange={(e) => save(key, options[Number(e.target.value)])}
+        >
+          {options.map((option, i) => (
+            <option key={i} value={String(i)}>
+              {option === null ? "" : String(option)}
+            </option>
+          ))}
+        </select>
+      );
+    }
+    if (type === "boolean") {
+      return (
+        <input
+          type="checkbox"
+          checked={current === true}
+          onChange={(e) => save(key, e.target.checked)}
+        />
+      );
+    }
+    if (type === "number" || type === "integer") {
+      return (
+        <input
+          key={inputKey}
+          type="number"
+          min={property.minimum}
+          max={property.maximum}
+          step={type === "integer" ? 1 : "any"}
+          defaultValue={typeof current === "number" ? current : ""}
+          onBlur={(e) => save(key, Number(e.target.value))}
+        />
+      );
+    }
+    if (type === "string") {
+      return (
+        <input
+          key={inputKey}
+          pattern={property.pattern}
+          defaultValue={typeof current === "string" ? current : ""}
+          onBlur={(e) => save(key, e.target.value)}
+        />
+      );
+    }
+    return (
+      <textarea
+        key={inputKey}
+        defaultValue={JSON.stringify(current ?? null)}
+        onBlur={(e) => {
+          try {
+            save(key, JSON.parse(e.target.value));
+          } catch {
+            setError(`${key} must be valid JSON`);
+          }
+        }}
+      />
+    );
+  };
+
+  return (
+    <div style={{ marginTop: 16 }}>
+      <h3>{schema.title ?? namespace}</h3>
+      {schema.description && <p>{schema.description}</p>}
+      {error && <p style={{ color: "red" }}>{error}</p>}
+      <div style={{ display: "flex", flexDirection: "column", gap: 8 }}>
+        {Object.entries(schema.properties).map(([key, property]) => (
+          <label key={key} title={property.description}>
+            {key}: {input(key, property)}
+          </label>
+        ))}
+      </div>
+    </div>
+  );
+}
@@ -1,17 +1,28 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import {
   getSetting,
   setSetting,
   loadSettings,
   saveSettings,
 } from "../lib/invoke";
+import { onSettingChanged, type SettingChanged } from "../lib/bindings";
+import { DEMO_SETTINGS_NAMESPACE } from "../lib/settings.generated";
+import { SettingsForm } from "./SettingsForm";
 
 export function SettingsPanel() {
-  const [namespace, setNamespace] = useState("demo");
+  const [namespace, setNamespace] = useState<string>(DEMO_SETTINGS_NAMESPACE);
   const [key, setKey] = useState("");
   const [value, setValue] = useState("");
   const [result, setResult] = useState<string | null>(null);
   const [error, setError] = useState<string | null>(null);
+  const [lastChange, setLastChange] = useState<SettingChanged | null>(null);
+
+  useEffect(() => {
+    const unlisten = onSettingChanged(setLastChange);
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
 
   const handleSet = () => {
     let parsed: unknown;
@@ -52,6 +63,12 @@ export function SettingsPanel() {
     <div style={{ border: "1px solid #ccc", borderRadius: 8, padding: 16 }}>
       <h2>Settings</h2>
       {error && <p style={{ color: "red" }}>{error}</p>}
+      {lastChange && (
+        <p>
+          Last change: {lastChange.namespace}.{lastChange.key} ={" "}
+          {JSON.stringify(lastChange.value)}
+        </p>
+      )}
       <div style={{ display: "flex", flexDirection: "column", gap: 8 }}>
         <label>
           Namespace:{" "}
@@ -74,6 +91,7 @@ export function SettingsPanel() {
           <button onClick={handleSaveBulk}>Save Bulk</button>
         </div>
       </div>
+      <SettingsForm namespace={namespace} />
       {result && (
         <pre
           style={{
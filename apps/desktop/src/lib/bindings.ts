@@ -1,9 +1,92 @@
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+
 // Matches shipkit_core::MigrationStatus
 export interface MigrationStatus {
+  set: string;
   version: number;
   name: string;
   applied: boolean;
   applied_at: string | null;
+  no_transaction: boolean;
+}
+
+// Matches shipkit_core::MigrationEvent
+export interface MigrationEvent {
+  id: number;
+  set: string;
+  version: number;
+  name: string;
+  kind: "apply" | "rollback" | "baseline";
+  success: boolean;
+  duration_ms: number;
+  app_version: string | null;
+  checksum: string;
+  down_checksum: string | null;
+  error: string | null;
+  occurred_at: string;
+}
+
+// Matches shipkit_core::db::MigrationProgress
+export type MigrationProgress = {
+  set: string;
+  version: number;
+  name: string;
+  index: number;
+  total: number;
+} & (
+  | { event: "started" }
+  | { event: "finished"; duration_ms: number }
+  | { event: "failed"; error: string }
+);
+
+// Matches shipkit_core::db::SeedStatus
+export interface SeedStatus {
+  name: string;
+  ran: boolean;
+  ran_at: string | null;
+}
+
+// Payload of the "settings://changed" event (shipkit_core::settings::changes::emit_to).
+// `value` is null after a delete.
+export interface SettingChanged {
+  namespace: string;
+  key: string;
+  value: unknown;
+}
+
+export const SETTINGS_CHANGED_EVENT = "settings://changed";
+
+// Listen for settings writes from any window or from Rust; optionally only
+// those in one namespace.
+export const onSettingChanged = (
+  handler: (change: SettingChanged) => void,
+  namespace?: string,
+): Promise<UnlistenFn> =>
+  listen<SettingChanged>(SETTINGS_CHANGED_EVENT, (e) => {
+    if (namespace === undefined || e.payload.namespace === namespace) {
+      handler(e.payload);
+    }
+  });
+
+// One field of a SettingsSchema (JSON Schema generated by #[derive(Settings)])
+export interface SettingsProperty {
+  type?: string | string[];
+  description?: string;
+  default?: unknown;
+  minimum?: number;
+  maximum?: number;
+  enum?: unknown[];
+  pattern?: string;
+  minLength?: number;
+  items?: SettingsProperty;
+}
+
+// Matches shipkit_core::settings::Settings::schema()
+export interface SettingsSchema {
+  title?: string;
+  description?: string;
+  type: "object";
+  properties: Record<string, SettingsProperty>;
 }
 
 // Matches shipkit_core::ThemeMode
@@ -1,5 +1,14 @@
 import { invoke as tauriInvoke } from "@tauri-apps/api/core";
-import type { MigrationStatus, ThemeDefinition, LogEntry } from "./bindings";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type {
+  MigrationStatus,
+  MigrationEvent,
+  MigrationProgress,
+  SeedStatus,
+  SettingsSchema,
+  ThemeDefinition,
+  LogEntry,
+} from "./bindings";
 
 // Database
 export const migrationStatus = () =>
@@ -11,6 +20,20 @@ export const applyMigrations = () =>
 export const rollbackMigration = () =>
   tauriInvoke<MigrationStatus | null>("rollback_migration");
 
+export const migrationHistory = () =>
+  tauriInvoke<MigrationEvent[]>("migration_history");
+
+export const seedStatus = () => tauriInvoke<SeedStatus[]>("seed_status");
+
+export const reseed = () => tauriInvoke<SeedStatus[]>("reseed");
+
+export const onMigrationProgress = (
+  handler: (progress: MigrationProgress) => void,
+): Promise<UnlistenFn> =>
+  listen<MigrationProgress>("shipkit://migration-progress", (e) =>
+    handler(e.payload),
+  );
+
 // Settings
 export const getSetting = (namespace: string, key: string) =>
   tauriInvoke<unknown | null>("get_setting", { namespace, key });
@@ -29,6 +52,9 @@ export const saveSettings = (
   settings: Record<string, unknown>,
 ) => tauriInvoke<void>("save_settings", { namespace, settings });
 
+export const getSettingsSchema = (namespace: string) =>
+  tauriInvoke<SettingsSchema>("get_settings_schema", { namespace });
+
 // Theme
 export const getTheme = () => tauriInvoke<ThemeDefinition>("get_theme");
 
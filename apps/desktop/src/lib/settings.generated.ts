@@ -0,0 +1,29 @@
+// Generated from the Rust settings types. Do not edit by hand.
+
+/**
+ * Sample preferences for the Settings panel.
+ *
+ * The panel renders a form for this namespace from its JSON Schema.
+ */
+export interface DemoSettings {
+  /** Shown at the top of the window. */
+  greeting: string;
+  /** Multiplier applied to every font size. */
+  font_scale: number;
+  /** Spacing between list items. */
+  density: "comfortable" | "compact";
+  /** Highlight color as a hex code. */
+  accent_color: string;
+  /** Show tips on startup. */
+  show_tips: boolean;
+}
+
+export const DEMO_SETTINGS_NAMESPACE = "demo";
+
+export const DEMO_SETTINGS_DEFAULTS: DemoSettings = {
+  greeting: "Welcome to ShipKit",
+  font_scale: 1.0,
+  density: "comfortable",
+  accent_color: "#3b82f6",
+  show_tips: true,
+};
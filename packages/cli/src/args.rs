@@ -0,0 +1,195 @@
+//! Command-line parsing for the `shipkit` binary.
+
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: shipkit migrate <command> [options]
+
+Commands:
+  new <name> [--timestamp]   Create an up/down migration file
+  status                     List migrations and whether they are applied
+  up [--to N]                Apply pending migrations, optionally up to version N
+  down [--steps N]           Roll back the last N migrations (default 1)
+  verify                     Check that every migration can be rolled back
+  docs [--format F]          Print the schema as markdown (default), dot or mermaid
+
+Options:
+  --db <path>    Database file (default: $SHIPKIT_DATABASE or data.db)
+  --dir <path>   Migrations directory (default: migrations)
+  -h, --help     Show this help
+";
+
+/// How `migrate new` numbers the file it creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionScheme {
+    /// One more than the highest existing version.
+    Sequence,
+    /// UTC time as `YYYYMMDDHHMMSS`.
+    Timestamp,
+}
+
+/// Output of `migrate docs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    New { name: String, scheme: VersionScheme },
+    Status,
+    Up { to: Option<i64> },
+    Down { steps: usize },
+    Verify,
+    Docs { format: DocFormat },
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    pub command: Command,
+    pub database: PathBuf,
+    pub migrations_dir: PathBuf,
+}
+
+/// Parse arguments (without the program name).
+///
+/// `default_database` is used when `--db` is not given.
+pub fn parse(args: &[String], default_database: PathBuf) -> Result<Args, String> {
+    let mut database = default_database;
+    let mut migrations_dir = PathBuf::from("migrations");
+    let mut scheme = VersionScheme::Sequence;
+    let mut to = None;
+    let mut steps = 1;
+    let mut format = DocFormat::Markdown;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |flag: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{flag} needs a value"))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(Args {
+                    command: Command::Help,
+                    database,
+                    migrations_dir,
+                });
+            }
+            "--db" => database = value("--db")?.into(),
+            "--dir" => migrations_dir = value("--dir")?.into(),
+            "--timestamp" => scheme = VersionScheme::Timestamp,
+            "--to" => to = Some(parse_number(&value("--to")?, "--to")?),
+            "--steps" => steps = parse_number(&value("--steps")?, "--steps")?,
+            "--format" => {
+                format = match value("--format")?.as_str() {
+                    "markdown" | "md" => DocFormat::Markdown,
+                    "dot" => DocFormat::Dot,
+                    "mermaid" => DocFormat::Mermaid,
+                    other => return Err(format!("unknown docs format: {other}")),
+                }
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option: {flag}")),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let command = match positional.as_slice() {
+        [] => Command::Help,
+        ["migrate", "new", name] => Command::New {
+            name: (*name).to_string(),
+            scheme,
+        },
+        ["migrate", "new"] => return Err("migrate new needs a name".into()),
+        ["migrate", "status"] => Command::Status,
+        ["migrate", "up"] => Command::Up { to },
+        ["migrate", "down"] => Command::Down { steps },
+        ["migrate", "verify"] => Command::Verify,
+        ["migrate", "docs"] => Command::Docs { format },
+        other => return Err(format!("unknown command: {}", other.join(" "))),
+    };
+
+    Ok(Args {
+        command,
+        database,
+        migrations_dir,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, flag: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{flag} expects a number, got {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Args, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse(&args, PathBuf::from("data.db"))
+    }
+
+    #[test]
+    fn parses_commands_with_options() {
+        let args = parse_args(&["migrate", "up", "--to", "3", "--db", "app.db"]).expect("parse");
+        assert_eq!(args.command, Command::Up { to: Some(3) });
+        assert_eq!(args.database, PathBuf::from("app.db"));
+        assert_eq!(args.migrations_dir, PathBuf::from("migrations"));
+
+        let args = parse_args(&["--dir", "sql", "migrate", "down", "--steps", "2"]).expect("parse");
+        assert_eq!(args.command, Command::Down { steps: 2 });
+        assert_eq!(args.migrations_dir, PathBuf::from("sql"));
+
+        let args = parse_args(&["migrate", "new", "add_tags", "--timestamp"]).expect("parse");
+        assert_eq!(
+            args.command,
+            Command::New {
+                name: "add_tags".into(),
+                scheme: VersionScheme::Timestamp,
+            }
+        );
+    }
+
+    #[test]
+    fn defaults_to_one_step_and_all_pending() {
+        assert_eq!(
+            parse_args(&["migrate", "down"]).expect("parse").command,
+            Command::Down { steps: 1 }
+        );
+        assert_eq!(
+            parse_args(&["migrate", "up"]).expect("parse").command,
+            Command::Up { to: None }
+        );
+        assert_eq!(
+            parse_args(&["migrate", "docs"]).expect("parse").command,
+            Command::Docs {
+                format: DocFormat::Markdown
+            }
+        );
+        assert_eq!(
+            parse_args(&["migrate", "docs", "--format", "mermaid"])
+                .expect("parse")
+                .command,
+            Command::Docs {
+                format: DocFormat::Mermaid
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse_args(&["migrate", "sideways"]).is_err());
+        assert!(parse_args(&["migrate", "up", "--to", "three"]).is_err());
+        assert!(parse_args(&["migrate", "down", "--steps"]).is_err());
+        assert!(parse_args(&["migrate", "new"]).is_err());
+        assert!(parse_args(&["migrate", "status", "--force"]).is_err());
+        assert!(parse_args(&["migrate", "docs", "--format", "svg"]).is_err());
+    }
+}
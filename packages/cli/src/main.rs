@@ -0,0 +1,173 @@
+//! `shipkit` — migration authoring and management from the command line.
+//!
+//! Operates on a database file and a directory of `NNN_name.sql` migrations
+//! through [`MigrationEngine`], so it sees exactly what the application sees.
+
+mod args;
+mod scaffold;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use shipkit_core::{ConnectionPool, MigrationEngine, MigrationStatus};
+
+use crate::args::{Args, Command, DocFormat, USAGE};
+
+fn main() -> ExitCode {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let default_database = std::env::var_os("SHIPKIT_DATABASE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("data.db"));
+
+    let args = match args::parse(&argv, default_database) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<ExitCode, String> {
+    let load = |pool: ConnectionPool| -> Result<MigrationEngine, String> {
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register_from_dir(&args.migrations_dir)
+            .map_err(|e| format!("{}: {e}", args.migrations_dir.display()))?;
+        Ok(engine)
+    };
+    // Only `up` creates the database, as the application does; opening a
+    // mistyped path for `status` or `down` would report every migration as
+    // pending instead
+    let engine = |create: bool| {
+        if !create && !args.database.exists() {
+            return Err(format!(
+                "{}: database not found (check --db or SHIPKIT_DATABASE)",
+                args.database.display()
+            ));
+        }
+        load(ConnectionPool::new(&args.database).map_err(|e| e.to_string())?)
+    };
+
+    match &args.command {
+        Command::Help => print!("{USAGE}"),
+        Command::New { name, scheme } => {
+            let path = scaffold::new_migration(&args.migrations_dir, name, *scheme)?;
+            println!("created {}", path.display());
+        }
+        Command::Status => {
+            let statuses = engine(false)?.status().map_err(|e| e.to_string())?;
+            print_status(&statuses);
+        }
+        Command::Up { to } => {
+            let mut engine = engine(true)?;
+            let before = applied_count(&engine.status().map_err(|e| e.to_string())?);
+            let statuses = match to {
+                Some(version) => engine.apply_to(*version),
+                None => engine.apply_pending(),
+            }
+            .map_err(|e| e.to_string())?;
+            println!("applied {} migration(s)", applied_count(&statuses) - before);
+            print_status(&statuses);
+        }
+        Command::Down { steps } => {
+            let mut engine = engine(false)?;
+            for _ in 0..*steps {
+                match engine.rollback_last().map_err(|e| e.to_string())? {
+                    Some(status) => println!("rolled back {} {}", status.version, status.name),
+                    None => {
+                        println!("nothing left to roll back");
+                        break;
+                    }
+                }
+            }
+        }
+        Command::Verify => {
+            // Needs only the migration files, so don't create the database
+            let pool = ConnectionPool::in_memory().map_err(|e| e.to_string())?;
+            let reports = load(pool)?.verify_reversible().map_err(|e| e.to_string())?;
+            let mut ok = true;
+            for report in &reports {
+                let outcome = if report.is_reversible() {
+                    "ok".to_string()
+                } else {
+                    ok = false;
+                    format!("{:?}", report.outcome)
+                };
+                println!("{:>14}  {:<32} {outcome}", report.version, report.name);
+            }
+            if !ok {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Docs { format } => {
+            // Needs only the migration files, so don't create the database
+            let pool = ConnectionPool::in_memory().map_err(|e| e.to_string())?;
+            let schema = load(pool)?.preview_schema().map_err(|e| e.to_string())?;
+            print!(
+                "{}",
+                match format {
+                    DocFormat::Markdown => schema.to_markdown(),
+                    DocFormat::Dot => schema.to_dot(),
+                    DocFormat::Mermaid => schema.to_mermaid(),
+                }
+            );
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn applied_count(statuses: &[MigrationStatus]) -> usize {
+    statuses.iter().filter(|s| s.applied).count()
+}
+
+fn print_status(statuses: &[MigrationStatus]) {
+    if statuses.is_empty() {
+        println!("no migrations found");
+        return;
+    }
+    for status in statuses {
+        let state = match &status.applied_at {
+            Some(applied_at) => format!("applied {applied_at}"),
+            None => "pending".to_string(),
+        };
+        println!("{:>14}  {:<32} {state}", status.version, status.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_and_down_need_an_existing_database() {
+        let tmp = tempfile::TempDir::new().expect("tmp dir");
+        let database = tmp.path().join("missing.db");
+        for command in [Command::Status, Command::Down { steps: 1 }] {
+            let err = run(Args {
+                command,
+                database: database.clone(),
+                migrations_dir: tmp.path().to_path_buf(),
+            })
+            .expect_err("no database");
+            assert!(err.contains("database not found"), "{err}");
+        }
+        assert!(!database.exists());
+
+        run(Args {
+            command: Command::Up { to: None },
+            database: database.clone(),
+            migrations_dir: tmp.path().to_path_buf(),
+        })
+        .expect("up creates the database");
+        assert!(database.exists());
+    }
+}
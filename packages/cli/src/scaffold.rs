@@ -0,0 +1,95 @@
+//! `migrate new`: create an empty migration file.
+
+use std::path::{Path, PathBuf};
+
+use crate::args::VersionScheme;
+
+/// Create `<version>_<name>.sql` in `dir` with empty up and down sections.
+///
+/// Sequence versions are zero-padded to three digits so files sort in
+/// version order. Returns the path of the new file.
+pub fn new_migration(dir: &Path, name: &str, scheme: VersionScheme) -> Result<PathBuf, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(format!(
+            "invalid migration name '{name}': use lowercase letters, digits and underscores"
+        ));
+    }
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+    let version = match scheme {
+        VersionScheme::Sequence => format!("{:03}", latest_version(dir)? + 1),
+        VersionScheme::Timestamp => chrono::Utc::now().format("%Y%m%d%H%M%S").to_string(),
+    };
+
+    let path = dir.join(format!("{version}_{name}.sql"));
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+    std::fs::write(&path, template(name))
+        .map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Highest version among the `NNN_name.sql` files in `dir`, or 0.
+fn latest_version(dir: &Path) -> Result<i64, String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("cannot read {}: {e}", dir.display()))?;
+    Ok(entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let file_name = e.file_name().to_string_lossy().into_owned();
+            let (version, _) = file_name.strip_suffix(".sql")?.split_once('_')?;
+            version.parse::<i64>().ok()
+        })
+        .max()
+        .unwrap_or(0))
+}
+
+/// Up and down sections in the layout `MigrationEngine::register_from_dir` reads.
+fn template(name: &str) -> String {
+    format!("-- {name}: up\n\n\n-- DOWN\n-- {name}: down\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sequence_versions_follow_existing_files() {
+        let dir = TempDir::new().expect("tempdir");
+        std::fs::write(dir.path().join("007_create_users.sql"), "").expect("existing file");
+        std::fs::write(dir.path().join("README.md"), "").expect("unrelated file");
+
+        let path =
+            new_migration(dir.path(), "add_tags", VersionScheme::Sequence).expect("new migration");
+        assert_eq!(
+            path.file_name().and_then(|n| n.to_str()),
+            Some("008_add_tags.sql")
+        );
+    }
+
+    #[test]
+    fn template_loads_as_migration_with_down_sql() {
+        let dir = TempDir::new().expect("tempdir");
+        new_migration(dir.path(), "create_notes", VersionScheme::Timestamp).expect("new migration");
+
+        let pool = shipkit_core::ConnectionPool::in_memory().expect("pool");
+        let mut engine = shipkit_core::MigrationEngine::new(pool);
+        engine.register_from_dir(dir.path()).expect("load");
+        assert_eq!(engine.migrations().len(), 1);
+        assert_eq!(engine.migrations()[0].name, "create_notes");
+        assert!(engine.migrations()[0].down_sql.is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let dir = TempDir::new().expect("tempdir");
+        assert!(new_migration(dir.path(), "Add Tags", VersionScheme::Sequence).is_err());
+        assert!(new_migration(dir.path(), "", VersionScheme::Sequence).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//! Schema documentation generated from registered migrations.
+//!
+//! [`MigrationEngine::preview_schema`] builds the schema the migrations
+//! produce on a scratch in-memory database, and [`DatabaseSchema`] renders it
+//! as Markdown, a Graphviz DOT graph or a Mermaid ER diagram. Generating docs
+//! from the engine keeps them in step with the migrations that ship:
+//!
+//! ```ignore
+//! let schema = engine.preview_schema()?;
+//! std::fs::write("docs/schema.md", schema.to_markdown())?;
+//! std::fs::write("docs/schema.dot", schema.to_dot())?;
+//! ```
+
+use std::fmt::Write;
+
+use crate::db::migration::{Migration, MigrationEngine};
+use crate::db::schema::{self, ColumnSchema, DatabaseSchema, ForeignKeySchema, TableSchema};
+use crate::db::verify;
+use crate::error::{Result, ShipKitError};
+
+impl MigrationEngine {
+    /// Schema produced by applying every registered migration to an empty
+    /// database.
+    ///
+    /// Runs on a private in-memory database, never the engine's own pool.
+    /// Sets are applied in dependency order, as [`apply_pending`] would.
+    ///
+    /// [`apply_pending`]: MigrationEngine::apply_pending
+    pub fn preview_schema(&self) -> Result<DatabaseSchema> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+
+        for set in self.sets()? {
+            for migration in &set.migrations {
+                verify::run(&conn, migration, &migration.up_sql)
+                    .map_err(|e| failed(&set.name, migration, e))?;
+            }
+        }
+
+        schema::inspect(&conn)
+    }
+}
+
+fn failed(set: &str, migration: &Migration, error: rusqlite::Error) -> ShipKitError {
+    ShipKitError::Migration(format!(
+        "migration {} ({}) in set '{set}' failed: {error}",
+        migration.version, migration.name
+    ))
+}
+
+impl DatabaseSchema {
+    /// Markdown reference: one section per table with a column table,
+    /// followed by indexes, views and triggers.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Database schema\n");
+
+        for table in &self.tables {
+            let _ = write!(out, "\n## {}\n\n", table.name);
+            out.push_str("| Column | Type | Nullable | Default | Key |\n");
+            out.push_str("|--------|------|----------|---------|-----|\n");
+            for column in &table.columns {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    markdown_cell(&column.name),
+                    markdown_cell(&column.data_type),
+                    if column.not_null { "no" } else { "yes" },
+                    markdown_cell(column.default_value.as_deref().unwrap_or("")),
+                    markdown_cell(&key_label(table, column)),
+                );
+            }
+
+            let indexes: Vec<_> = self
+                .indexes
+                .iter()
+                .filter(|i| i.table == table.name)
+                .collect();
+            if !indexes.is_empty() {
+                out.push_str("\nIndexes:\n\n");
+                for index in indexes {
+                    let unique = if index.unique { ", unique" } else { "" };
+                    let _ = writeln!(
+                        out,
+                        "- `{}` ({}){unique}",
+                        index.name,
+                        index.columns.join(", ")
+                    );
+                }
+            }
+        }
+
+        for (heading, objects) in [("Views", &self.views), ("Triggers", &self.triggers)] {
+            if objects.is_empty() {
+                continue;
+            }
+            let _ = write!(out, "\n## {heading}\n\n");
+            for (name, sql) in objects {
+                let _ = writeln!(out, "- `{name}`: `{sql}`");
+            }
+        }
+
+        out
+    }
+
+    /// Graphviz DOT graph with one node per table (columns listed in the
+    /// label) and an edge per foreign key column.
+    pub fn to_dot(&self) -> String {
+        let mut out =
+            String::from("digraph schema {\n    rankdir=LR;\n    node [shape=plaintext];\n");
+
+        for table in &self.tables {
+            let _ = write!(
+                out,
+                "\n    \"{}\" [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\">\
+                 <tr><td bgcolor=\"lightgrey\"><b>{}</b></td></tr>",
+                dot_id(&table.name),
+                html_escape(&table.name)
+            );
+            for column in &table.columns {
+                let mut label = column.name.clone();
+                if !column.data_type.is_empty() {
+                    label.push(' ');
+                    label.push_str(&column.data_type);
+                }
+                let key = key_label(table, column);
+                if !key.is_empty() {
+                    label.push_str(" (");
+                    label.push_str(&key);
+                    label.push(')');
+                }
+                let _ = write!(
+                    out,
+                    "<tr><td port=\"{}\" align=\"left\">{}</td></tr>",
+                    html_escape(&column.name),
+                    html_escape(&label)
+                );
+            }
+            out.push_str("</table>>];\n");
+        }
+
+        for table in &self.tables {
+            for fk in &table.foreign_keys {
+                let target = match &fk.references_column {
+                    Some(column) => {
+                        format!(
+                            "\"{}\":\"{}\"",
+                            dot_id(&fk.references_table),
+                            dot_id(column)
+                        )
+                    }
+                    None => format!("\"{}\"", dot_id(&fk.references_table)),
+                };
+                let _ = writeln!(
+                    out,
+                    "    \"{}\":\"{}\" -> {target};",
+                    dot_id(&table.name),
+                    dot_id(&fk.column)
+                );
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid `erDiagram` with each table's columns and a relationship per
+    /// foreign key column.
+    ///
+    /// Mermaid only accepts word characters and hyphens in names and types,
+    /// so anything else (spaces, `DECIMAL(10,2)`) becomes `_`.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("erDiagram\n");
+
+        for table in &self.tables {
+            let _ = writeln!(out, "    {} {{", mermaid_name(&table.name));
+            for column in &table.columns {
+                let data_type = if column.data_type.is_empty() {
+                    "ANY".to_string()
+                } else {
+                    mermaid_name(&column.data_type)
+                };
+                let mut keys = Vec::new();
+                if column.primary_key > 0 {
+                    keys.push("PK");
+                }
+                if foreign_key(table, column).is_some() {
+                    keys.push("FK");
+                }
+                let _ = writeln!(
+                    out,
+                    "        {data_type} {}{}",
+                    mermaid_name(&column.name),
+                    if keys.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" {}", keys.join(", "))
+                    }
+                );
+            }
+            out.push_str("    }\n");
+        }
+
+        for table in &self.tables {
+            for fk in &table.foreign_keys {
+                let nullable = table
+                    .columns
+                    .iter()
+                    .find(|c| c.name == fk.column)
+                    .is_none_or(|c| !c.not_null);
+                let parent = if nullable { "|o" } else { "||" };
+                let _ = writeln!(
+                    out,
+                    "    {} {parent}--o{{ {} : \"{}\"",
+                    mermaid_name(&fk.references_table),
+                    mermaid_name(&table.name),
+                    fk.column.replace('"', "'")
+                );
+            }
+        }
+
+        out
+    }
+}
+
+fn foreign_key<'a>(table: &'a TableSchema, column: &ColumnSchema) -> Option<&'a ForeignKeySchema> {
+    table
+        .foreign_keys
+        .iter()
+        .find(|fk| fk.column == column.name)
+}
+
+/// `PK`, `FK → table.column`, both, or empty.
+fn key_label(table: &TableSchema, column: &ColumnSchema) -> String {
+    let mut keys = Vec::new();
+    if column.primary_key > 0 {
+        keys.push("PK".to_string());
+    }
+    if let Some(fk) = foreign_key(table, column) {
+        keys.push(match &fk.references_column {
+            Some(target) => format!("FK → {}.{target}", fk.references_table),
+            None => format!("FK → {}", fk.references_table),
+        });
+    }
+    keys.join(", ")
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn dot_id(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn mermaid_name(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migration::MigrationSet;
+    use crate::db::pool::ConnectionPool;
+
+    fn engine() -> MigrationEngine {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_teams".into(),
+                up_sql: "CREATE TABLE teams (id INTEGER PRIMARY KEY, name TEXT NOT NULL);".into(),
+                down_sql: None,
+            })
+            .register(Migration {
+                version: 2,
+                name: "create_users".into(),
+                up_sql: "CREATE TABLE users (
+                    id INTEGER PRIMARY KEY,
+                    email TEXT NOT NULL DEFAULT '',
+                    team_id INTEGER REFERENCES teams(id)
+                );
+                CREATE UNIQUE INDEX idx_users_email ON users(email);"
+                    .into(),
+                down_sql: None,
+            });
+        engine
+    }
+
+    #[test]
+    fn preview_applies_every_set_without_touching_the_pool() {
+        let mut engine = engine();
+        let mut audit = MigrationSet::new("audit");
+        audit.migrations.push(Migration {
+            version: 1,
+            name: "create_audit_log".into(),
+            up_sql: "CREATE TABLE audit_log (id INTEGER PRIMARY KEY, entry TEXT);".into(),
+            down_sql: None,
+        });
+        engine.register_set(audit);
+
+        let schema = engine.preview_schema().expect("preview");
+        let names: Vec<_> = schema.tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["audit_log", "teams", "users"]);
+        assert!(engine.status().expect("status").iter().all(|s| !s.applied));
+    }
+
+    #[test]
+    fn preview_reports_the_failing_migration() {
+        let mut engine = engine();
+        engine.register(Migration {
+            version: 3,
+            name: "broken".into(),
+            up_sql: "CREATE TABLE users (id INTEGER);".into(),
+            down_sql: None,
+        });
+
+        let err = engine.preview_schema().expect_err("duplicate table");
+        assert!(
+            matches!(err, ShipKitError::Migration(ref m) if m.starts_with("migration 3 (broken)"))
+        );
+    }
+
+    #[test]
+    fn markdown_lists_columns_keys_and_indexes() {
+        let markdown = engine().preview_schema().expect("preview").to_markdown();
+        assert!(markdown.contains("## users"));
+        assert!(markdown.contains("| id | INTEGER | yes |  | PK |"));
+        assert!(markdown.contains("| email | TEXT | no | '' |  |"));
+        assert!(markdown.contains("| team_id | INTEGER | yes |  | FK → teams.id |"));
+        assert!(markdown.contains("- `idx_users_email` (email), unique"));
+    }
+
+    #[test]
+    fn dot_has_a_node_per_table_and_an_edge_per_foreign_key() {
+        let dot = engine().preview_schema().expect("preview").to_dot();
+        assert!(dot.starts_with("digraph schema {"));
+        assert!(dot.contains("\"teams\" [label=<"));
+        assert!(
+            dot.contains(
+                "<td port=\"team_id\" align=\"left\">team_id INTEGER (FK → teams.id)</td>"
+            )
+        );
+        assert!(dot.contains("\"users\":\"team_id\" -> \"teams\":\"id\";"));
+    }
+
+    #[test]
+    fn mermaid_declares_entities_and_relationships() {
+        let mermaid = engine().preview_schema().expect("preview").to_mermaid();
+        assert!(mermaid.starts_with("erDiagram\n"));
+        assert!(mermaid.contains("    users {\n        INTEGER id PK\n"));
+        assert!(mermaid.contains("        INTEGER team_id FK\n"));
+        assert!(mermaid.contains("    teams |o--o{ users : \"team_id\""));
+    }
+
+    #[test]
+    fn mermaid_replaces_punctuation_in_types() {
+        let mut engine = engine();
+        engine.register(Migration {
+            version: 3,
+            name: "create_invoices".into(),
+            up_sql: "CREATE TABLE invoices (id INTEGER PRIMARY KEY, total DECIMAL(10,2));".into(),
+            down_sql: None,
+        });
+
+        let mermaid = engine.preview_schema().expect("preview").to_mermaid();
+        assert!(mermaid.contains("        DECIMAL_10_2_ total\n"));
+    }
+}
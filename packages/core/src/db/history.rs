@@ -0,0 +1,201 @@
+//! Append-only audit log of migration applies and rollbacks.
+
+use std::time::Duration;
+
+use crate::db::migration::add_column_if_missing;
+use crate::error::Result;
+
+/// What happened to a migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationEventKind {
+    Apply,
+    Rollback,
+    /// Marked as applied by [`super::MigrationEngine::baseline`] without running.
+    Baseline,
+}
+
+impl MigrationEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Apply => "apply",
+            Self::Rollback => "rollback",
+            Self::Baseline => "baseline",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "apply" => Some(Self::Apply),
+            "rollback" => Some(Self::Rollback),
+            "baseline" => Some(Self::Baseline),
+            _ => None,
+        }
+    }
+}
+
+/// One row of `_shipkit_migration_history`.
+///
+/// Rows are never updated or deleted, so the log survives rollbacks and
+/// records failed attempts alongside successful ones.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MigrationEvent {
+    pub id: i64,
+    pub set: String,
+    pub version: i64,
+    pub name: String,
+    pub kind: MigrationEventKind,
+    pub success: bool,
+    pub duration_ms: i64,
+    /// Version of the application that ran the migration, if configured.
+    pub app_version: Option<String>,
+    pub checksum: String,
+    pub down_checksum: Option<String>,
+    /// Error message for failed attempts.
+    pub error: Option<String>,
+    pub occurred_at: String,
+}
+
+/// Fields of a new history row; the id and timestamp are filled in by SQLite.
+pub(crate) struct NewEvent<'a> {
+    pub set: &'a str,
+    pub version: i64,
+    pub name: &'a str,
+    pub kind: MigrationEventKind,
+    pub duration: Duration,
+    pub app_version: Option<&'a str>,
+    pub checksum: String,
+    pub down_checksum: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Create the history table if needed.
+pub(crate) fn ensure_history_table(conn: &rusqlite::Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS _shipkit_migration_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            \"set\" TEXT NOT NULL DEFAULT 'app',
+            version INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            success INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            app_version TEXT,
+            checksum TEXT NOT NULL,
+            down_checksum TEXT,
+            error TEXT,
+            occurred_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )?;
+    // Logs written before migration sets existed
+    add_column_if_missing(
+        conn,
+        "_shipkit_migration_history",
+        "set",
+        "TEXT NOT NULL DEFAULT 'app'",
+    )
+}
+
+/// Append an event.
+pub(crate) fn record(conn: &rusqlite::Connection, event: NewEvent<'_>) -> Result<()> {
+    conn.execute(
+        "INSERT INTO _shipkit_migration_history
+            (\"set\", version, name, kind, success, duration_ms, app_version, checksum,
+             down_checksum, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        rusqlite::params![
+            event.set,
+            event.version,
+            event.name,
+            event.kind.as_str(),
+            event.error.is_none(),
+            i64::try_from(event.duration.as_millis()).unwrap_or(i64::MAX),
+            event.app_version,
+            event.checksum,
+            event.down_checksum,
+            event.error,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Id of the newest event, or 0 when the log is empty.
+pub(crate) fn last_id(conn: &rusqlite::Connection) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COALESCE(MAX(id), 0) FROM _shipkit_migration_history",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// Write `events` back with their original ids and timestamps, e.g. after
+/// restoring a backup taken before they were recorded.
+pub(crate) fn reinsert(conn: &rusqlite::Connection, events: &[MigrationEvent]) -> Result<()> {
+    for event in events {
+        conn.execute(
+            "INSERT OR IGNORE INTO _shipkit_migration_history
+                (id, \"set\", version, name, kind, success, duration_ms, app_version,
+                 checksum, down_checksum, error, occurred_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            rusqlite::params![
+                event.id,
+                event.set,
+                event.version,
+                event.name,
+                event.kind.as_str(),
+                event.success,
+                event.duration_ms,
+                event.app_version,
+                event.checksum,
+                event.down_checksum,
+                event.error,
+                event.occurred_at,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// All events, oldest first.
+pub(crate) fn load(conn: &rusqlite::Connection) -> Result<Vec<MigrationEvent>> {
+    load_since(conn, 0)
+}
+
+/// Events with an id greater than `id`, oldest first.
+pub(crate) fn load_since(conn: &rusqlite::Connection, id: i64) -> Result<Vec<MigrationEvent>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, version, name, kind, success, duration_ms, app_version,
+                checksum, down_checksum, error, occurred_at, \"set\"
+         FROM _shipkit_migration_history WHERE id > ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map([id], |row| {
+        let kind: String = row.get(3)?;
+        let kind = MigrationEventKind::parse(&kind).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                3,
+                rusqlite::types::Type::Text,
+                format!("unknown migration event kind: {kind}").into(),
+            )
+        })?;
+        Ok(MigrationEvent {
+            id: row.get(0)?,
+            set: row.get(11)?,
+            version: row.get(1)?,
+            name: row.get(2)?,
+            kind,
+            success: row.get(4)?,
+            duration_ms: row.get(5)?,
+            app_version: row.get(6)?,
+            checksum: row.get(7)?,
+            down_checksum: row.get(8)?,
+            error: row.get(9)?,
+            occurred_at: row.get(10)?,
+        })
+    })?;
+
+    let mut events = Vec::new();
+    for row in rows {
+        events.push(row?);
+    }
+    Ok(events)
+}
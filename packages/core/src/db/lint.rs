@@ -0,0 +1,479 @@
+//! Static checks for risky statements in migration SQL.
+//!
+//! Like the rest of ShipKit's SQL handling this is a token scan, not a
+//! parser: it looks at the leading keywords of each statement and is meant to
+//! catch mistakes in ordinary migration scripts before release.
+//!
+//! Nothing runs the lint automatically; there is no compile-time hook. Call
+//! it from a test so warnings fail CI:
+//!
+//! ```ignore
+//! #[test]
+//! fn migrations_are_clean() {
+//!     let warnings = shipkit_core::db::lint::lint_dir("migrations").unwrap();
+//!     assert!(warnings.is_empty(), "{warnings:#?}");
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::db::migration::{Migration, MigrationEngine, REBUILD_PREFIX};
+use crate::db::schema::{tokenize, unquote};
+use crate::error::Result;
+
+/// Comment that silences rules for one migration, e.g.
+/// `-- shipkit:allow drop-table, drop-column`.
+const ALLOW_DIRECTIVE: &str = "-- shipkit:allow";
+
+/// What a [`LintWarning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    /// `DROP TABLE` destroys data.
+    DropTable,
+    /// `ALTER TABLE ... DROP COLUMN` destroys data.
+    DropColumn,
+    /// `DELETE` without a `WHERE` clause empties the table.
+    DeleteWithoutWhere,
+    /// `ALTER TABLE ... RENAME` can break views and triggers that refer to
+    /// the old name.
+    Rename,
+    /// `CREATE TABLE/INDEX/VIEW/TRIGGER` without `IF NOT EXISTS` fails when
+    /// the object already exists, e.g. on a database adopted with a baseline.
+    MissingIfNotExists,
+    /// The migration cannot be rolled back.
+    MissingDownSql,
+}
+
+impl LintRule {
+    /// Name used in `-- shipkit:allow` comments.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::DropTable => "drop-table",
+            Self::DropColumn => "drop-column",
+            Self::DeleteWithoutWhere => "delete-without-where",
+            Self::Rename => "rename",
+            Self::MissingIfNotExists => "missing-if-not-exists",
+            Self::MissingDownSql => "missing-down-sql",
+        }
+    }
+}
+
+/// A risky pattern found in a migration.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LintWarning {
+    pub version: i64,
+    pub name: String,
+    pub rule: LintRule,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "migration {} ({}): {}: {}",
+            self.version,
+            self.name,
+            self.rule.code(),
+            self.message
+        )
+    }
+}
+
+impl Migration {
+    /// Check this migration on its own. See [`lint_all`].
+    pub fn lint(&self) -> Vec<LintWarning> {
+        lint_all(std::slice::from_ref(self))
+    }
+}
+
+impl MigrationEngine {
+    /// Check every registered migration, set by set. See [`lint_all`].
+    pub fn lint(&self) -> Result<Vec<LintWarning>> {
+        Ok(self
+            .sets()?
+            .into_iter()
+            .flat_map(|set| lint_all(&set.migrations))
+            .collect())
+    }
+}
+
+/// Check the `.sql` migrations in `dir`, in the format read by
+/// [`Migration::from_dir`].
+pub fn lint_dir(dir: impl AsRef<Path>) -> Result<Vec<LintWarning>> {
+    Ok(lint_all(&Migration::from_dir(dir)?))
+}
+
+/// Check a sequence of migrations in the order they are applied.
+///
+/// Views and triggers created by earlier migrations are remembered, so a
+/// rename warning names the ones that mention the renamed table. Dropping a
+/// temp table created earlier in the same script, and the drop and rename of
+/// a [`Migration::rebuild_table`], are not flagged. Rules listed in a
+/// `-- shipkit:allow` comment in a migration's up SQL are skipped for that
+/// migration.
+pub fn lint_all(migrations: &[Migration]) -> Vec<LintWarning> {
+    let mut dependents = BTreeMap::new();
+    let mut warnings = Vec::new();
+    for migration in migrations {
+        let allowed = allowed_rules(&migration.up_sql);
+        let mut warn = |rule: LintRule, message: String| {
+            if !allowed.contains(&rule.code()) {
+                warnings.push(LintWarning {
+                    version: migration.version,
+                    name: migration.name.clone(),
+                    rule,
+                    message,
+                });
+            }
+        };
+
+        let mut script = Script::default();
+        for statement in statements(&migration.up_sql) {
+            check_statement(&statement, &mut dependents, &mut script, &mut warn);
+        }
+        if migration.down_sql.is_none() {
+            warn(LintRule::MissingDownSql, "has no down SQL".into());
+        }
+    }
+    warnings
+}
+
+/// Views and triggers seen so far: name to (kind, lowercase tokens).
+type Dependents = BTreeMap<String, (&'static str, Vec<String>)>;
+
+/// Tables created earlier in the same script, by lowercase name, whose
+/// later drop or rename loses nothing.
+#[derive(Default)]
+struct Script {
+    /// `CREATE TEMP TABLE`s, which vanish with the connection anyway.
+    temp_tables: BTreeSet<String>,
+    /// Tables being rebuilt: a `_shipkit_new_<table>` copy has been created,
+    /// so dropping `<table>` and renaming the copy into place is the
+    /// [`TableRebuild`](crate::db::migration::TableRebuild) procedure.
+    rebuilds: BTreeSet<String>,
+}
+
+fn check_statement(
+    tokens: &[String],
+    dependents: &mut Dependents,
+    script: &mut Script,
+    warn: &mut impl FnMut(LintRule, String),
+) {
+    let words: Vec<String> = tokens.iter().map(|t| t.to_ascii_uppercase()).collect();
+    let is = |i: usize, word: &str| words.get(i).is_some_and(|w| w == word);
+    let name_at = |i: usize| tokens.get(i).map(|t| unquote(t)).unwrap_or_default();
+
+    if is(0, "CREATE") {
+        let mut i = 1;
+        if is(i, "TEMP") || is(i, "TEMPORARY") {
+            // Temporary objects vanish with the connection
+            if is(i + 1, "TABLE") {
+                let if_not_exists = is(i + 2, "IF") && is(i + 3, "NOT") && is(i + 4, "EXISTS");
+                let name = name_at(if if_not_exists { i + 5 } else { i + 2 });
+                script.temp_tables.insert(name.to_lowercase());
+            }
+            return;
+        }
+        if is(i, "UNIQUE") {
+            i += 1;
+        }
+        let Some(kind) = ["TABLE", "INDEX", "VIEW", "TRIGGER"]
+            .into_iter()
+            .find(|kind| is(i, kind))
+        else {
+            return;
+        };
+        let kind = kind.to_ascii_lowercase();
+        let if_not_exists = is(i + 1, "IF") && is(i + 2, "NOT") && is(i + 3, "EXISTS");
+        let name = name_at(if if_not_exists { i + 4 } else { i + 1 });
+        if kind == "table"
+            && let Some(table) = name.to_lowercase().strip_prefix(REBUILD_PREFIX)
+        {
+            // The rebuild copy replaces `table`, which was checked when created
+            script.rebuilds.insert(table.to_string());
+            return;
+        }
+        if !if_not_exists {
+            warn(
+                LintRule::MissingIfNotExists,
+                format!("creates {kind} {name} without IF NOT EXISTS"),
+            );
+        }
+        if let Some(kind) = ["view", "trigger"].into_iter().find(|k| *k == kind) {
+            let body = tokens.iter().map(|t| unquote(t).to_lowercase()).collect();
+            dependents.insert(name.to_lowercase(), (kind, body));
+        }
+    } else if is(0, "DROP") {
+        let i = if is(2, "IF") && is(3, "EXISTS") { 4 } else { 2 };
+        let name = name_at(i);
+        let lower = name.to_lowercase();
+        if is(1, "TABLE") {
+            if !script.temp_tables.remove(&lower) && !script.rebuilds.contains(&lower) {
+                warn(LintRule::DropTable, format!("drops table {name}"));
+            }
+        } else if is(1, "VIEW") || is(1, "TRIGGER") {
+            dependents.remove(&lower);
+        }
+    } else if is(0, "ALTER") && is(1, "TABLE") {
+        let table = name_at(2);
+        if is(3, "DROP") {
+            let column = name_at(if is(4, "COLUMN") { 5 } else { 4 });
+            warn(
+                LintRule::DropColumn,
+                format!("drops column {column} from {table}"),
+            );
+        } else if is(3, "RENAME") {
+            // Moving a rebuild's copy into place keeps the original name
+            let target = name_at(5).to_lowercase();
+            if is(4, "TO")
+                && table.to_lowercase() == format!("{REBUILD_PREFIX}{target}")
+                && script.rebuilds.remove(&target)
+            {
+                return;
+            }
+            let what = if is(4, "TO") {
+                format!("renames table {table} to {}", name_at(5))
+            } else {
+                let i = if is(4, "COLUMN") { 5 } else { 4 };
+                format!("renames column {} of {table}", name_at(i))
+            };
+            let lower = table.to_lowercase();
+            let affected: Vec<String> = dependents
+                .iter()
+                .filter(|(_, (_, body))| body.contains(&lower))
+                .map(|(name, (kind, _))| format!("{kind} {name}"))
+                .collect();
+            let message = if affected.is_empty() {
+                format!("{what}; views and triggers using the old name may break")
+            } else {
+                format!("{what}, which is used by {}", affected.join(", "))
+            };
+            warn(LintRule::Rename, message);
+        }
+    } else if is(0, "DELETE") && !words.iter().any(|w| w == "WHERE") {
+        let i = if is(1, "FROM") { 2 } else { 1 };
+        warn(
+            LintRule::DeleteWithoutWhere,
+            format!("deletes every row of {}", name_at(i)),
+        );
+    }
+}
+
+/// Split a script into tokenized statements, keeping a trigger's
+/// `BEGIN ... END` body (which contains semicolons) in one statement.
+fn statements(sql: &str) -> Vec<Vec<String>> {
+    let mut statements = Vec::new();
+    let mut trigger: Option<Vec<String>> = None;
+    for piece in sql.split(';') {
+        let tokens = tokenize(piece);
+        if let Some(body) = trigger.as_mut() {
+            let ends = tokens
+                .first()
+                .is_some_and(|t| t.eq_ignore_ascii_case("END"));
+            body.extend(tokens);
+            if ends {
+                statements.extend(trigger.take());
+            }
+            continue;
+        }
+        if tokens.is_empty() {
+            continue;
+        }
+        let creates_trigger = tokens[0].eq_ignore_ascii_case("CREATE")
+            && tokens
+                .iter()
+                .take(4)
+                .any(|t| t.eq_ignore_ascii_case("TRIGGER"));
+        if creates_trigger {
+            trigger = Some(tokens);
+        } else {
+            statements.push(tokens);
+        }
+    }
+    statements.extend(trigger);
+    statements
+}
+
+/// Rule codes listed in `-- shipkit:allow` comments.
+fn allowed_rules(sql: &str) -> Vec<&str> {
+    sql.lines()
+        .filter_map(|line| line.trim().strip_prefix(ALLOW_DIRECTIVE))
+        .flat_map(|rules| rules.split([',', ' ']))
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(version: i64, up_sql: &str) -> Migration {
+        Migration {
+            version,
+            name: format!("m{version}"),
+            up_sql: up_sql.into(),
+            down_sql: Some("-- no-op".into()),
+        }
+    }
+
+    fn rules(warnings: &[LintWarning]) -> Vec<LintRule> {
+        warnings.iter().map(|w| w.rule).collect()
+    }
+
+    #[test]
+    fn flags_destructive_statements() {
+        let warnings = migration(
+            1,
+            "DROP TABLE IF EXISTS old_users;
+             ALTER TABLE users DROP COLUMN legacy;
+             DELETE FROM sessions;
+             DELETE FROM tokens WHERE expired = 1;",
+        )
+        .lint();
+        assert_eq!(
+            rules(&warnings),
+            [
+                LintRule::DropTable,
+                LintRule::DropColumn,
+                LintRule::DeleteWithoutWhere
+            ]
+        );
+        assert_eq!(warnings[0].message, "drops table old_users");
+        assert_eq!(warnings[2].message, "deletes every row of sessions");
+    }
+
+    #[test]
+    fn flags_create_without_if_not_exists() {
+        let warnings = migration(
+            1,
+            "CREATE TABLE a (id INTEGER PRIMARY KEY);
+             CREATE TABLE IF NOT EXISTS b (id INTEGER PRIMARY KEY);
+             CREATE UNIQUE INDEX idx_a ON a(id);
+             CREATE TEMP TABLE scratch (id INTEGER);",
+        )
+        .lint();
+        let messages: Vec<&str> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "creates table a without IF NOT EXISTS",
+                "creates index idx_a without IF NOT EXISTS"
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_names_dependent_views_and_triggers() {
+        let warnings = lint_all(&[
+            migration(
+                1,
+                "CREATE VIEW IF NOT EXISTS active_users AS SELECT * FROM users WHERE active = 1;
+                 CREATE TRIGGER IF NOT EXISTS users_audit AFTER DELETE ON users BEGIN
+                     DELETE FROM audit WHERE user_id = old.id;
+                 END;",
+            ),
+            migration(2, "ALTER TABLE users RENAME TO accounts;"),
+        ]);
+        assert_eq!(rules(&warnings), [LintRule::Rename]);
+        assert_eq!(
+            warnings[0].message,
+            "renames table users to accounts, which is used by view active_users, trigger users_audit"
+        );
+    }
+
+    #[test]
+    fn trigger_bodies_are_not_linted_as_statements() {
+        let warnings = migration(
+            1,
+            "CREATE TRIGGER IF NOT EXISTS purge AFTER INSERT ON logs BEGIN
+                 DELETE FROM cache;
+             END;",
+        )
+        .lint();
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn table_rebuilds_are_not_flagged() {
+        use crate::db::migration::TableRebuild;
+
+        let rebuild = |after_sql| {
+            let nullable = TableRebuild {
+                table: "users",
+                definition: "id INTEGER PRIMARY KEY, name TEXT",
+                columns: &["id", "name"],
+                after_sql: "",
+            };
+            Migration {
+                down_sql: Some(nullable.to_sql()),
+                ..Migration::rebuild_table(
+                    2,
+                    "users_name_not_null",
+                    TableRebuild {
+                        definition: "id INTEGER PRIMARY KEY, name TEXT NOT NULL",
+                        after_sql,
+                        ..nullable
+                    },
+                )
+            }
+        };
+        let warnings = lint_all(&[rebuild("")]);
+        assert!(warnings.is_empty(), "{warnings:?}");
+
+        // Statements the caller adds are still checked
+        let warnings = rebuild("DROP TABLE sessions;").lint();
+        assert_eq!(rules(&warnings), [LintRule::DropTable]);
+        assert_eq!(warnings[0].message, "drops table sessions");
+    }
+
+    #[test]
+    fn dropping_a_temp_table_from_the_same_script_is_not_flagged() {
+        let warnings = migration(
+            1,
+            "CREATE TEMP TABLE scratch (id INTEGER);
+             DROP TABLE scratch;
+             DROP TABLE scratch;",
+        )
+        .lint();
+        assert_eq!(rules(&warnings), [LintRule::DropTable]);
+    }
+
+    #[test]
+    fn flags_missing_down_sql_and_honours_allow_comments() {
+        let mut m = migration(
+            1,
+            "-- shipkit:allow drop-table\nDROP TABLE old; DELETE FROM logs;",
+        );
+        m.down_sql = None;
+        assert_eq!(
+            rules(&m.lint()),
+            [LintRule::DeleteWithoutWhere, LintRule::MissingDownSql]
+        );
+
+        m.up_sql = "-- shipkit:allow drop-table, delete-without-where missing-down-sql\n\
+                    DROP TABLE old; DELETE FROM logs;"
+            .into();
+        assert!(m.lint().is_empty());
+    }
+
+    #[test]
+    fn lints_a_migrations_directory() {
+        let dir = tempfile::TempDir::new().expect("tempdir");
+        std::fs::write(
+            dir.path().join("001_drop_users.sql"),
+            "DROP TABLE users;\n-- DOWN\nCREATE TABLE users (id INTEGER PRIMARY KEY);\n",
+        )
+        .expect("write migration");
+
+        let warnings = lint_dir(dir.path()).expect("lint");
+        assert_eq!(
+            warnings[0].to_string(),
+            "migration 1 (drop_users): drop-table: drops table users"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Cross-process lock held while the migration engine changes the schema.
+
+use std::time::{Duration, Instant};
+
+use crate::db::pool::ConnectionPool;
+use crate::error::{Result, ShipKitError};
+
+/// How often a waiting process re-checks the lock.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Create the single-row lock table if needed.
+pub(crate) fn ensure_lock_table(conn: &rusqlite::Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS _shipkit_migration_lock (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            owner TEXT NOT NULL,
+            acquired_at INTEGER NOT NULL
+        );",
+    )?;
+    Ok(())
+}
+
+/// An acquired row in `_shipkit_migration_lock`. Released on drop.
+pub(crate) struct MigrationLock<'a> {
+    pool: &'a ConnectionPool,
+    owner: String,
+}
+
+impl<'a> MigrationLock<'a> {
+    /// Take the lock, waiting up to `timeout` for another holder to release it.
+    ///
+    /// A lock older than `stale_after` is assumed to belong to a crashed
+    /// process and is broken with a warning.
+    pub(crate) fn acquire(
+        pool: &'a ConnectionPool,
+        timeout: Duration,
+        stale_after: Duration,
+    ) -> Result<Self> {
+        let owner = format!(
+            "pid {} ({:?}, {})",
+            std::process::id(),
+            std::thread::current().id(),
+            chrono::Utc::now().to_rfc3339()
+        );
+        let stale_secs = i64::try_from(stale_after.as_secs()).unwrap_or(i64::MAX);
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let conn = pool.get()?;
+
+            let holder = conn.query_row(
+                "SELECT owner, acquired_at, CAST(strftime('%s', 'now') AS INTEGER) - acquired_at
+                 FROM _shipkit_migration_lock WHERE id = 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                },
+            );
+            let holder = match holder {
+                Ok(holder) => Some(holder),
+                Err(rusqlite::Error::QueryReturnedNoRows) => None,
+                Err(e) => return Err(e.into()),
+            };
+
+            if let Some((stale_owner, _, age)) = &holder
+                && *age >= stale_secs
+            {
+                let broken = conn.execute(
+                    "DELETE FROM _shipkit_migration_lock WHERE id = 1 AND owner = ?1",
+                    [stale_owner],
+                )?;
+                if broken > 0 {
+                    tracing::warn!(
+                        owner = %stale_owner,
+                        age_secs = age,
+                        "broke stale migration lock"
+                    );
+                }
+                continue;
+            }
+
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO _shipkit_migration_lock (id, owner, acquired_at)
+                 VALUES (1, ?1, CAST(strftime('%s', 'now') AS INTEGER))",
+                [&owner],
+            )?;
+            if inserted == 1 {
+                tracing::debug!(owner = %owner, "acquired migration lock");
+                return Ok(Self { pool, owner });
+            }
+
+            if Instant::now() >= deadline {
+                let (owner, acquired_at) = match holder {
+                    Some((owner, acquired_at, _)) => (owner, acquired_at),
+                    None => ("unknown".to_string(), 0),
+                };
+                let acquired_at = chrono::DateTime::from_timestamp(acquired_at, 0)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default();
+                return Err(ShipKitError::MigrationLocked { owner, acquired_at });
+            }
+
+            drop(conn);
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl MigrationLock<'_> {
+    /// Reset the lock's age so a long run is not mistaken for a crashed one.
+    ///
+    /// Fails if the lock has been broken by another process in the meantime.
+    pub(crate) fn heartbeat(&self) -> Result<()> {
+        let conn = self.pool.get()?;
+        let refreshed = conn.execute(
+            "UPDATE _shipkit_migration_lock SET acquired_at = CAST(strftime('%s', 'now') AS INTEGER)
+             WHERE id = 1 AND owner = ?1",
+            [&self.owner],
+        )?;
+        if refreshed == 0 {
+            return Err(ShipKitError::Migration(format!(
+                "migration lock held by {} was broken by another process",
+                self.owner
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MigrationLock<'_> {
+    fn drop(&mut self) {
+        let released = self.pool.get().and_then(|conn| {
+            conn.execute(
+                "DELETE FROM _shipkit_migration_lock WHERE id = 1 AND owner = ?1",
+                [&self.owner],
+            )?;
+            Ok(())
+        });
+        if let Err(e) = released {
+            tracing::warn!(owner = %self.owner, error = %e, "failed to release migration lock");
+        }
+    }
+}
@@ -1,13 +1,37 @@
 //! Database migration engine with checksum verification.
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use rusqlite::backup::Progress;
 use sha2::{Digest, Sha256};
 
+use crate::db::history::{self, MigrationEvent, MigrationEventKind, NewEvent};
+use crate::db::lock::{self, MigrationLock};
 use crate::db::pool::ConnectionPool;
+use crate::db::progress::{MigrationProgress, ProgressObserver};
+use crate::db::schema;
 use crate::error::{Result, ShipKitError};
 
+/// Header line that marks a `.sql` migration file as non-transactional.
+const NO_TRANSACTION_DIRECTIVE: &str = "-- shipkit:no-transaction";
+
+/// Set that [`MigrationEngine::register`] and
+/// [`MigrationEngine::register_from_dir`] add migrations to.
+pub const DEFAULT_SET: &str = "app";
+
+/// Columns of `_shipkit_migrations`.
+const TRACKING_COLUMNS: &str = "
+    \"set\" TEXT NOT NULL DEFAULT 'app',
+    version INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    applied_at TEXT NOT NULL DEFAULT (datetime('now')),
+    checksum_algorithm TEXT NOT NULL DEFAULT 'sha256',
+    PRIMARY KEY (\"set\", version)
+";
+
 /// A single database migration.
 pub struct Migration {
     pub version: i64,
@@ -16,44 +40,52 @@ pub struct Migration {
     pub down_sql: Option<String>,
 }
 
-/// Status of a migration (applied or pending).
-#[derive(Debug, Clone, serde::Serialize)]
-pub struct MigrationStatus {
-    pub version: i64,
-    pub name: String,
-    pub applied: bool,
-    pub applied_at: Option<String>,
-}
-
-/// Manages schema migrations with ordering, checksums, and rollback.
-pub struct MigrationEngine {
-    pool: ConnectionPool,
-    migrations: Vec<Migration>,
-}
-
-impl MigrationEngine {
-    /// Create a new engine. Does not create the tracking table yet.
-    pub fn new(pool: ConnectionPool) -> Self {
+impl Migration {
+    /// A migration with no down script.
+    pub fn new(version: i64, name: impl Into<String>, up_sql: impl Into<String>) -> Self {
         Self {
-            pool,
-            migrations: Vec::new(),
+            version,
+            name: name.into(),
+            up_sql: up_sql.into(),
+            down_sql: None,
         }
     }
 
-    /// Register a migration. Returns `&mut Self` for chaining.
-    pub fn register(&mut self, migration: Migration) -> &mut Self {
-        self.migrations.push(migration);
-        self.migrations.sort_by_key(|m| m.version);
+    /// Run `up_sql` outside the engine's transaction.
+    ///
+    /// Needed for statements SQLite refuses inside a transaction (`VACUUM`,
+    /// `PRAGMA journal_mode`, `PRAGMA foreign_keys`). The migration is only
+    /// recorded as applied once every statement has succeeded. This prepends
+    /// the same `-- shipkit:no-transaction` line that marks `.sql` files.
+    ///
+    /// The engine does not check foreign keys afterwards, and restores the
+    /// connection's `foreign_keys` setting. A migration that switches foreign
+    /// keys off should check them itself before its `COMMIT`, as
+    /// [`Self::rebuild_table`] does.
+    pub fn no_transaction(mut self) -> Self {
+        if !self.is_no_transaction() {
+            self.up_sql = format!("{NO_TRANSACTION_DIRECTIVE}\n{}", self.up_sql);
+        }
         self
     }
 
-    /// Load migrations from a directory of `.sql` files.
+    /// Whether `up_sql` runs outside the engine's transaction.
+    pub fn is_no_transaction(&self) -> bool {
+        self.up_sql
+            .lines()
+            .next()
+            .is_some_and(|line| line.trim() == NO_TRANSACTION_DIRECTIVE)
+    }
+
+    /// Read migrations from a directory of `.sql` files, ordered by version.
     ///
     /// Files must be named `{NNN}_{name}.sql` where NNN is a numeric version.
     /// If a file contains a line `-- DOWN` by itself, everything after it is the
-    /// down migration.
-    pub fn register_from_dir(&mut self, dir: impl AsRef<Path>) -> Result<&mut Self> {
+    /// down migration. A first line of `-- shipkit:no-transaction` marks the
+    /// migration as non-transactional.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Vec<Self>> {
         let dir = dir.as_ref();
+        let mut migrations = Vec::new();
         let mut entries: Vec<_> = std::fs::read_dir(dir)?
             .filter_map(|e| e.ok())
             .filter(|e| {
@@ -93,7 +125,7 @@ impl MigrationEngine {
                 (content, None)
             };
 
-            self.migrations.push(Migration {
+            migrations.push(Migration {
                 version,
                 name: name.to_string(),
                 up_sql,
@@ -101,373 +133,2702 @@ impl MigrationEngine {
             });
         }
 
-        self.migrations.sort_by_key(|m| m.version);
-        Ok(self)
+        migrations.sort_by_key(|m| m.version);
+        Ok(migrations)
     }
 
-    /// Apply all pending migrations. Returns status of all migrations.
-    pub fn apply_pending(&mut self) -> Result<Vec<MigrationStatus>> {
-        self.ensure_tracking_table()?;
-        let applied = self.get_applied()?;
+    /// Build a non-transactional migration that rebuilds `table` using
+    /// SQLite's 12-step "foreign keys off" procedure.
+    ///
+    /// See [`TableRebuild`] for what the generated SQL does.
+    pub fn rebuild_table(version: i64, name: impl Into<String>, rebuild: TableRebuild<'_>) -> Self {
+        Self::new(version, name, rebuild.to_sql()).no_transaction()
+    }
+}
 
-        for migration in &self.migrations {
-            if let Some(existing_checksum) = applied.get(&migration.version) {
-                let current_checksum = Self::checksum(&migration.up_sql);
-                if *existing_checksum != current_checksum {
-                    return Err(ShipKitError::Migration(format!(
-                        "checksum mismatch for migration {}: {}",
-                        migration.version, migration.name
-                    )));
-                }
-                continue; // already applied
-            }
+/// An independently versioned group of migrations, typically one per module.
+///
+/// Versions only have to be unique within a set, so a reusable module can
+/// number its migrations from 1 without colliding with the application's.
+/// Sets are applied in dependency order, and the [`DEFAULT_SET`] implicitly
+/// depends on every other registered set.
+pub struct MigrationSet {
+    pub name: String,
+    /// Sets whose migrations must all be applied before this set's.
+    pub depends_on: Vec<String>,
+    pub migrations: Vec<Migration>,
+}
 
-            let conn = self.pool.get()?;
-            let tx = conn.unchecked_transaction()?;
-            match tx.execute_batch(&migration.up_sql) {
-                Ok(()) => {
-                    tx.execute(
-                        "INSERT INTO _shipkit_migrations (version, name, checksum) VALUES (?1, ?2, ?3)",
-                        rusqlite::params![
-                            migration.version,
-                            migration.name,
-                            Self::checksum(&migration.up_sql),
-                        ],
-                    )?;
-                    tx.commit()?;
-                }
-                Err(e) => {
-                    // Transaction rolls back on drop
-                    return Err(ShipKitError::Migration(format!(
-                        "migration {} ({}) failed: {e}",
-                        migration.version, migration.name
-                    )));
-                }
-            }
+impl MigrationSet {
+    /// Create an empty set with no dependencies.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            depends_on: Vec::new(),
+            migrations: Vec::new(),
         }
-
-        self.status()
     }
+}
 
-    /// Rollback the most recently applied migration.
-    pub fn rollback_last(&mut self) -> Result<Option<MigrationStatus>> {
-        self.ensure_tracking_table()?;
-        let applied = self.get_applied()?;
+/// Prefix of the table a [`TableRebuild`] fills before renaming it into
+/// place.
+pub(crate) const REBUILD_PREFIX: &str = "_shipkit_new_";
 
-        // Find the highest applied version
-        let last_version = applied.keys().max().copied();
-        let Some(last_version) = last_version else {
-            return Ok(None);
-        };
+/// Parameters for the standard SQLite table rebuild.
+///
+/// SQLite's `ALTER TABLE` cannot change column types or constraints, so the
+/// table is recreated: foreign keys are switched off, a new table is created
+/// and filled from the old one, the old table is dropped and the new one
+/// renamed into place, then foreign keys are switched back on. Foreign keys
+/// from and to the rebuilt table are checked before the rebuild commits, so
+/// one that leaves dangling references is rolled back and not recorded as
+/// applied. Violations elsewhere in the database are left alone.
+#[derive(Debug, Clone, Copy)]
+pub struct TableRebuild<'a> {
+    /// Table being rebuilt.
+    pub table: &'a str,
+    /// Column and constraint definitions for the new table, without the
+    /// surrounding parentheses.
+    pub definition: &'a str,
+    /// Columns copied from the old table. They must exist in both versions.
+    pub columns: &'a [&'a str],
+    /// SQL run after the rename, e.g. recreating indexes, triggers and views.
+    pub after_sql: &'a str,
+}
 
-        let migration = self
-            .migrations
+impl TableRebuild<'_> {
+    /// Render the rebuild as a SQL script.
+    pub fn to_sql(&self) -> String {
+        let table = quote_ident(self.table);
+        let temp = quote_ident(&format!("{REBUILD_PREFIX}{}", self.table));
+        let columns = self
+            .columns
             .iter()
-            .find(|m| m.version == last_version)
-            .ok_or_else(|| {
-                ShipKitError::Migration(format!(
-                    "migration {last_version} is applied but not registered"
-                ))
-            })?;
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
 
-        let down_sql = migration.down_sql.as_ref().ok_or_else(|| {
-            ShipKitError::Migration(format!(
-                "migration {} ({}) has no down SQL",
-                migration.version, migration.name
-            ))
-        })?;
+        let mut sql = String::from("PRAGMA foreign_keys=OFF;\nBEGIN;\n");
+        sql.push_str(&format!("CREATE TABLE {temp} ({});\n", self.definition));
+        if !self.columns.is_empty() {
+            sql.push_str(&format!(
+                "INSERT INTO {temp} ({columns}) SELECT {columns} FROM {table};\n"
+            ));
+        }
+        sql.push_str(&format!("DROP TABLE {table};\n"));
+        sql.push_str(&format!("ALTER TABLE {temp} RENAME TO {table};\n"));
+        if !self.after_sql.trim().is_empty() {
+            sql.push_str(self.after_sql.trim());
+            sql.push('\n');
+        }
+        // Step 10 of the procedure: a dangling reference fails the CHECK,
+        // which aborts the script while the transaction is still open.
+        let name = quote_literal(self.table);
+        sql.push_str(&format!(
+            "CREATE TEMP TABLE _shipkit_fk_check (violations INTEGER NOT NULL \
+             CONSTRAINT \"foreign key violation\" CHECK (violations = 0));\n\
+             INSERT INTO _shipkit_fk_check SELECT count(*) FROM pragma_foreign_key_check \
+             WHERE \"table\" = {name} OR parent = {name};\n\
+             DROP TABLE _shipkit_fk_check;\n",
+        ));
+        sql.push_str("COMMIT;\nPRAGMA foreign_keys=ON;\n");
+        sql
+    }
+}
 
-        let conn = self.pool.get()?;
-        let tx = conn.unchecked_transaction()?;
-        tx.execute_batch(down_sql)?;
-        tx.execute(
-            "DELETE FROM _shipkit_migrations WHERE version = ?1",
-            rusqlite::params![last_version],
-        )?;
-        tx.commit()?;
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
 
-        Ok(Some(MigrationStatus {
-            version: migration.version,
-            name: migration.name.clone(),
-            applied: false,
-            applied_at: None,
-        }))
-    }
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
 
-    /// Get the status of all registered migrations.
-    pub fn status(&self) -> Result<Vec<MigrationStatus>> {
-        self.ensure_tracking_table()?;
-        let conn = self.pool.get()?;
-        let mut stmt =
-            conn.prepare("SELECT version, applied_at FROM _shipkit_migrations")?;
-        let applied: HashMap<i64, String> = stmt
-            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
-            .filter_map(|r| r.ok())
-            .collect();
+/// Run one of `migration`'s scripts (up or down) on `conn` the way the engine
+/// does, and return a transaction to record the result in.
+///
+/// Transactional migrations run `sql` inside the returned transaction, so the
+/// script and the caller's bookkeeping commit together. No-transaction
+/// migrations run `sql` directly on the connection: a transaction the script
+/// leaves open is rolled back and the connection's `foreign_keys` setting is
+/// restored, since pooled connections only get their pragmas applied once.
+/// The returned transaction then only covers the bookkeeping.
+pub(crate) fn run_script<'c>(
+    conn: &'c rusqlite::Connection,
+    migration: &Migration,
+    sql: &str,
+) -> rusqlite::Result<rusqlite::Transaction<'c>> {
+    if !migration.is_no_transaction() {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        return Ok(tx);
+    }
 
-        Ok(self
-            .migrations
-            .iter()
-            .map(|m| MigrationStatus {
-                version: m.version,
-                name: m.name.clone(),
-                applied: applied.contains_key(&m.version),
-                applied_at: applied.get(&m.version).cloned(),
-            })
-            .collect())
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+    let result = conn.execute_batch(sql);
+    if !conn.is_autocommit() {
+        conn.execute_batch("ROLLBACK;")?;
     }
+    conn.pragma_update(None, "foreign_keys", foreign_keys)?;
+    result?;
+    conn.unchecked_transaction()
+}
 
-    fn ensure_tracking_table(&self) -> Result<()> {
-        let conn = self.pool.get()?;
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS _shipkit_migrations (
-                version INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                checksum TEXT NOT NULL,
-                applied_at TEXT NOT NULL DEFAULT (datetime('now'))
-            );",
-        )?;
-        Ok(())
+/// How migration checksums are computed.
+///
+/// The algorithm is stored next to each checksum in `_shipkit_migrations`, so
+/// rows are always verified with the mode they were recorded under. Switching
+/// modes only affects newly applied migrations until [`MigrationEngine::repair_checksums`]
+/// rewrites the existing rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChecksumMode {
+    /// SHA-256 over the exact bytes of `up_sql`.
+    #[default]
+    Exact,
+    /// SHA-256 over `up_sql` with comments removed and whitespace (including
+    /// line endings) collapsed, so reformatting does not change the checksum.
+    /// String literals and quoted identifiers are left untouched.
+    Normalized,
+}
+
+impl ChecksumMode {
+    /// Marker stored in the `checksum_algorithm` column.
+    pub fn algorithm(self) -> &'static str {
+        match self {
+            Self::Exact => "sha256",
+            Self::Normalized => "sha256-normalized",
+        }
     }
 
-    fn get_applied(&self) -> Result<HashMap<i64, String>> {
-        let conn = self.pool.get()?;
-        let mut stmt =
-            conn.prepare("SELECT version, checksum FROM _shipkit_migrations")?;
-        let map: HashMap<i64, String> = stmt
-            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
-            .filter_map(|r| r.ok())
-            .collect();
-        Ok(map)
+    /// Parse a stored `checksum_algorithm` marker.
+    pub fn from_algorithm(algorithm: &str) -> Option<Self> {
+        match algorithm {
+            "sha256" => Some(Self::Exact),
+            "sha256-normalized" => Some(Self::Normalized),
+            _ => None,
+        }
     }
 
-    fn checksum(sql: &str) -> String {
+    /// Compute the checksum of `sql` under this mode.
+    pub fn checksum(self, sql: &str) -> String {
         let mut hasher = Sha256::new();
-        hasher.update(sql.as_bytes());
+        match self {
+            Self::Exact => hasher.update(sql.as_bytes()),
+            Self::Normalized => hasher.update(normalize_sql(sql).as_bytes()),
+        }
         format!("{:x}", hasher.finalize())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
-
-    fn test_pool() -> ConnectionPool {
-        ConnectionPool::in_memory().expect("in-memory pool")
+/// Strip comments and whitespace outside quoted text.
+///
+/// Whitespace is kept (as a single space) only where it separates two words,
+/// so `t (id)` and `t(id)` normalize the same way.
+pub(crate) fn normalize_sql(sql: &str) -> String {
+    fn is_word(c: char) -> bool {
+        c.is_alphanumeric() || matches!(c, '_' | '$' | '\'' | '"' | '`')
     }
 
-    #[test]
-    fn apply_single_migration() {
-        let pool = test_pool();
-        let mut engine = MigrationEngine::new(pool.clone());
-        engine.register(Migration {
-            version: 1,
-            name: "create_users".into(),
-            up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);".into(),
-            down_sql: Some("DROP TABLE users;".into()),
-        });
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut pending_space = false;
 
-        let statuses = engine.apply_pending().expect("apply");
-        assert_eq!(statuses.len(), 1);
-        assert!(statuses[0].applied);
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                pending_space = true;
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                pending_space = true;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                pending_space = true;
+                continue;
+            }
+            _ => {}
+        }
 
-        // Verify table exists
-        let conn = pool.get().expect("conn");
-        conn.execute("INSERT INTO users (name) VALUES ('test')", [])
-            .expect("insert into created table");
+        if pending_space && out.chars().next_back().is_some_and(is_word) && is_word(c) {
+            out.push(' ');
+        }
+        pending_space = false;
+        out.push(c);
+
+        if matches!(c, '\'' | '"' | '`') {
+            // A doubled quote is an escape; it reads as close + reopen here.
+            for inner in chars.by_ref() {
+                out.push(inner);
+                if inner == c {
+                    break;
+                }
+            }
+        }
     }
+    out
+}
 
-    #[test]
-    fn apply_multiple_migrations() {
-        let pool = test_pool();
-        let mut engine = MigrationEngine::new(pool.clone());
-        engine
-            .register(Migration {
-                version: 1,
-                name: "create_users".into(),
-                up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY);".into(),
-                down_sql: Some("DROP TABLE users;".into()),
-            })
-            .register(Migration {
-                version: 2,
-                name: "create_posts".into(),
-                up_sql: "CREATE TABLE posts (id INTEGER PRIMARY KEY);".into(),
-                down_sql: Some("DROP TABLE posts;".into()),
-            })
-            .register(Migration {
-                version: 3,
-                name: "create_comments".into(),
-                up_sql: "CREATE TABLE comments (id INTEGER PRIMARY KEY);".into(),
-                down_sql: Some("DROP TABLE comments;".into()),
-            });
+/// A stored checksum rewritten by [`MigrationEngine::repair_checksums`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChecksumRepair {
+    pub set: String,
+    pub version: i64,
+    pub name: String,
+    pub old_checksum: String,
+    pub old_algorithm: String,
+    pub new_checksum: String,
+    pub new_algorithm: String,
+}
 
-        let statuses = engine.apply_pending().expect("apply");
-        assert_eq!(statuses.len(), 3);
-        assert!(statuses.iter().all(|s| s.applied));
-    }
+/// How [`MigrationEngine::apply_pending`] treats history that does not line up
+/// with the registered migrations.
+///
+/// Drift is checked separately within each registered set; rows belonging to
+/// sets the engine does not know about are left alone. Three kinds of drift
+/// are detected before anything is applied:
+/// - **out-of-order** — a pending migration has a lower version than the
+///   latest applied one ([`ShipKitError::MigrationOutOfOrder`]);
+/// - **gap** — an applied version is no longer registered but sits between
+///   registered versions, e.g. a deleted migration file ([`ShipKitError::MigrationGap`]);
+/// - **orphaned** — an applied version is not registered and lies below the
+///   registered range, e.g. squashed history ([`ShipKitError::MigrationOrphaned`]).
+///
+/// Applied versions above the registered range mean a newer build migrated
+/// the database; that case is governed by [`DowngradePolicy`] instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MigrationPolicy {
+    /// Reject every kind of drift.
+    #[default]
+    Strict,
+    /// Apply out-of-order migrations; reject gaps and orphans.
+    AllowOutOfOrder,
+    /// Tolerate gaps and orphans; reject out-of-order migrations.
+    IgnoreMissing,
+}
 
-    #[test]
-    fn idempotent_apply() {
-        let pool = test_pool();
-        let mut engine = MigrationEngine::new(pool);
-        engine.register(Migration {
-            version: 1,
-            name: "create_t".into(),
-            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
-            down_sql: None,
-        });
+/// What the engine does with a database migrated by a newer build, i.e. one
+/// whose applied versions go beyond every registered migration in a set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DowngradePolicy {
+    /// Refuse to migrate with [`ShipKitError::SchemaTooNew`].
+    #[default]
+    Refuse,
+    /// Leave the database untouched and let [`MigrationEngine::apply_pending`]
+    /// succeed, so the application can check
+    /// [`MigrationEngine::compatibility`] and open it read-only. Rollbacks are
+    /// still refused.
+    ReadOnly,
+}
 
-        engine.apply_pending().expect("first apply");
-        let statuses = engine.apply_pending().expect("second apply");
-        assert_eq!(statuses.len(), 1);
-        assert!(statuses[0].applied);
+/// How the database relates to the registered migrations.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SchemaCompatibility {
+    /// No set has applied versions beyond those registered.
+    Compatible,
+    /// A newer build applied migrations this build does not know about.
+    Newer {
+        set: String,
+        database_version: i64,
+        supported_version: i64,
+    },
+}
+
+impl SchemaCompatibility {
+    fn into_error(self) -> Option<ShipKitError> {
+        match self {
+            Self::Compatible => None,
+            Self::Newer {
+                set,
+                database_version,
+                supported_version,
+            } => Some(ShipKitError::SchemaTooNew {
+                set,
+                database_version,
+                supported_version,
+            }),
+        }
     }
+}
 
-    #[test]
-    fn rollback_last() {
-        let pool = test_pool();
-        let mut engine = MigrationEngine::new(pool.clone());
-        engine
-            .register(Migration {
-                version: 1,
-                name: "create_a".into(),
-                up_sql: "CREATE TABLE a (id INTEGER PRIMARY KEY);".into(),
-                down_sql: Some("DROP TABLE a;".into()),
-            })
-            .register(Migration {
-                version: 2,
-                name: "create_b".into(),
-                up_sql: "CREATE TABLE b (id INTEGER PRIMARY KEY);".into(),
-                down_sql: Some("DROP TABLE b;".into()),
-            });
+/// Which pending migrations a run applies.
+#[derive(Clone, Copy)]
+enum ApplyScope<'a> {
+    All,
+    /// Every other set in full, and the [`DEFAULT_SET`] up to a version.
+    UpTo(i64),
+    /// One set and the sets it depends on.
+    Set(&'a str),
+}
 
-        engine.apply_pending().expect("apply");
+/// A row of `_shipkit_migrations`.
+struct AppliedMigration {
+    checksum: String,
+    algorithm: String,
+}
 
-        let rolled_back = engine.rollback_last().expect("rollback");
-        assert!(rolled_back.is_some());
-        assert_eq!(rolled_back.as_ref().map(|s| s.version), Some(2));
+/// Status of a migration (applied or pending).
+///
+/// A [`Migration::no_transaction`] migration that fails part-way is reported as
+/// not applied, but the statements that ran before the failure are **not**
+/// undone. Such a migration should be written to be safe to re-run (for
+/// example with `IF NOT EXISTS`), or the database repaired by hand before
+/// retrying.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MigrationStatus {
+    /// Migration set the migration belongs to.
+    pub set: String,
+    pub version: i64,
+    pub name: String,
+    pub applied: bool,
+    pub applied_at: Option<String>,
+    /// Whether the migration runs outside a transaction.
+    pub no_transaction: bool,
+}
 
-        let statuses = engine.status().expect("status");
-        assert!(statuses[0].applied);
-        assert!(!statuses[1].applied);
+/// Manages schema migrations with ordering, checksums, and rollback.
+pub struct MigrationEngine {
+    pool: ConnectionPool,
+    sets: Vec<MigrationSet>,
+    checksum_mode: ChecksumMode,
+    policy: MigrationPolicy,
+    downgrade_policy: DowngradePolicy,
+    backup_dir: Option<PathBuf>,
+    backups_to_keep: usize,
+    lock_timeout: Duration,
+    stale_lock_after: Duration,
+    app_version: Option<String>,
+    progress: Option<ProgressObserver>,
+}
 
-        // Verify table b is gone
-        let conn = pool.get().expect("conn");
-        let err = conn.execute("INSERT INTO b (id) VALUES (1)", []);
-        assert!(err.is_err());
+impl MigrationEngine {
+    /// Create a new engine. Does not create the tracking table yet.
+    pub fn new(pool: ConnectionPool) -> Self {
+        Self {
+            pool,
+            sets: Vec::new(),
+            checksum_mode: ChecksumMode::default(),
+            policy: MigrationPolicy::default(),
+            downgrade_policy: DowngradePolicy::default(),
+            backup_dir: None,
+            backups_to_keep: 5,
+            lock_timeout: Duration::from_secs(30),
+            stale_lock_after: Duration::from_secs(10 * 60),
+            app_version: None,
+            progress: None,
+        }
     }
 
-    #[test]
-    fn rollback_without_down_sql() {
-        let pool = test_pool();
+    /// Called as each pending migration starts, finishes or fails in
+    /// [`apply_pending`](Self::apply_pending). Returns `&mut Self` for chaining.
+    pub fn set_progress_observer(
+        &mut self,
+        observer: impl Fn(&MigrationProgress) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.progress = Some(Box::new(observer));
+        self
+    }
+
+    /// Application version stored with each entry in the migration history.
+    /// Returns `&mut Self` for chaining.
+    pub fn set_app_version(&mut self, version: impl Into<String>) -> &mut Self {
+        self.app_version = Some(version.into());
+        self
+    }
+
+    /// How long to wait for another process holding the migration lock
+    /// (default 30 seconds). Returns `&mut Self` for chaining.
+    pub fn set_lock_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Age after which a held migration lock is assumed to belong to a
+    /// crashed process and broken (default 10 minutes). The holder refreshes
+    /// the lock before each migration, so this bounds a single migration's
+    /// run time rather than the whole run's.
+    /// Returns `&mut Self` for chaining.
+    pub fn set_stale_lock_after(&mut self, age: Duration) -> &mut Self {
+        self.stale_lock_after = age;
+        self
+    }
+
+    /// Snapshot the database into `dir` before applying pending migrations,
+    /// and restore that snapshot if any of them fail.
+    ///
+    /// Only the newest `pre-migration-*.db` snapshots are kept (5 by default,
+    /// see [`Self::set_backups_to_keep`]); older ones are deleted after each
+    /// new snapshot is taken. Other files in `dir` are left alone.
+    /// Returns `&mut Self` for chaining.
+    pub fn set_backup_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.backup_dir = Some(dir.into());
+        self
+    }
+
+    /// How many pre-migration snapshots to keep in the backup directory
+    /// (default 5, at least 1). Returns `&mut Self` for chaining.
+    pub fn set_backups_to_keep(&mut self, count: usize) -> &mut Self {
+        self.backups_to_keep = count.max(1);
+        self
+    }
+
+    /// Choose how drift between applied and registered migrations is handled.
+    /// Returns `&mut Self` for chaining.
+    pub fn set_policy(&mut self, policy: MigrationPolicy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Choose what happens when the database was migrated by a newer build.
+    /// Returns `&mut Self` for chaining.
+    pub fn set_downgrade_policy(&mut self, policy: DowngradePolicy) -> &mut Self {
+        self.downgrade_policy = policy;
+        self
+    }
+
+    /// Choose how checksums of newly applied migrations are computed.
+    /// Returns `&mut Self` for chaining.
+    pub fn set_checksum_mode(&mut self, mode: ChecksumMode) -> &mut Self {
+        self.checksum_mode = mode;
+        self
+    }
+
+    /// Register a migration in the [`DEFAULT_SET`]. Returns `&mut Self` for chaining.
+    pub fn register(&mut self, migration: Migration) -> &mut Self {
+        let set = self.set_mut(DEFAULT_SET);
+        set.migrations.push(migration);
+        set.migrations.sort_by_key(|m| m.version);
+        self
+    }
+
+    /// Register a migration set. A set registered under an existing name is
+    /// merged into it. Returns `&mut Self` for chaining.
+    pub fn register_set(&mut self, set: MigrationSet) -> &mut Self {
+        let existing = self.set_mut(&set.name);
+        for dependency in set.depends_on {
+            if !existing.depends_on.contains(&dependency) {
+                existing.depends_on.push(dependency);
+            }
+        }
+        existing.migrations.extend(set.migrations);
+        existing.migrations.sort_by_key(|m| m.version);
+        self
+    }
+
+    fn set_mut(&mut self, name: &str) -> &mut MigrationSet {
+        let index = match self.sets.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.sets.push(MigrationSet::new(name));
+                self.sets.len() - 1
+            }
+        };
+        &mut self.sets[index]
+    }
+
+    /// Migrations registered in the [`DEFAULT_SET`], ordered by version.
+    pub fn migrations(&self) -> &[Migration] {
+        self.sets
+            .iter()
+            .find(|s| s.name == DEFAULT_SET)
+            .map_or(&[], |s| &s.migrations)
+    }
+
+    /// Registered sets in the order they are applied.
+    ///
+    /// Fails if a set depends on a set that is not registered, or if the
+    /// dependencies form a cycle.
+    pub fn sets(&self) -> Result<Vec<&MigrationSet>> {
+        let dependencies = |set: &MigrationSet| -> Vec<String> {
+            if set.name == DEFAULT_SET {
+                self.sets
+                    .iter()
+                    .filter(|s| s.name != DEFAULT_SET)
+                    .map(|s| s.name.clone())
+                    .collect()
+            } else {
+                set.depends_on.clone()
+            }
+        };
+
+        for set in &self.sets {
+            if let Some(unknown) = set
+                .depends_on
+                .iter()
+                .find(|d| !self.sets.iter().any(|s| &s.name == *d))
+            {
+                return Err(ShipKitError::Migration(format!(
+                    "migration set '{}' depends on unknown set '{unknown}'",
+                    set.name
+                )));
+            }
+        }
+
+        let mut ordered: Vec<&MigrationSet> = Vec::with_capacity(self.sets.len());
+        while ordered.len() < self.sets.len() {
+            let next = self.sets.iter().find(|set| {
+                !ordered.iter().any(|o| o.name == set.name)
+                    && dependencies(set)
+                        .iter()
+                        .all(|d| ordered.iter().any(|o| &o.name == d))
+            });
+            match next {
+                Some(set) => ordered.push(set),
+                None => {
+                    let remaining: Vec<&str> = self
+                        .sets
+                        .iter()
+                        .filter(|s| !ordered.iter().any(|o| o.name == s.name))
+                        .map(|s| s.name.as_str())
+                        .collect();
+                    return Err(ShipKitError::Migration(format!(
+                        "migration sets have a dependency cycle: {}",
+                        remaining.join(", ")
+                    )));
+                }
+            }
+        }
+        Ok(ordered)
+    }
+
+    /// Load migrations from a directory of `.sql` files into the [`DEFAULT_SET`].
+    ///
+    /// See [`Migration::from_dir`] for the file format.
+    pub fn register_from_dir(&mut self, dir: impl AsRef<Path>) -> Result<&mut Self> {
+        let set = self.set_mut(DEFAULT_SET);
+        set.migrations.extend(Migration::from_dir(dir)?);
+        set.migrations.sort_by_key(|m| m.version);
+        Ok(self)
+    }
+
+    /// Apply all pending migrations, set by set in dependency order.
+    /// Returns status of all migrations.
+    ///
+    /// Holds the cross-process migration lock for the whole run, so two
+    /// processes starting at once cannot apply the same migration twice.
+    pub fn apply_pending(&mut self) -> Result<Vec<MigrationStatus>> {
+        self.apply(ApplyScope::All)
+    }
+
+    /// Like [`apply_pending`](Self::apply_pending), but stop after `version`
+    /// in the [`DEFAULT_SET`]. Other sets are still applied in full.
+    pub fn apply_to(&mut self, version: i64) -> Result<Vec<MigrationStatus>> {
+        self.apply(ApplyScope::UpTo(version))
+    }
+
+    /// Like [`apply_pending`](Self::apply_pending), but only for the set
+    /// `name` and the sets it depends on, e.g. to create a module's tables
+    /// before the rest of the application is migrated.
+    pub fn apply_set(&mut self, name: &str) -> Result<Vec<MigrationStatus>> {
+        if !self.sets.iter().any(|s| s.name == name) {
+            return Err(ShipKitError::Migration(format!(
+                "unknown migration set '{name}'"
+            )));
+        }
+        self.apply(ApplyScope::Set(name))
+    }
+
+    /// `name` and every set it depends on, directly or not.
+    fn with_dependencies(&self, name: &str) -> BTreeSet<String> {
+        let mut names = BTreeSet::from([name.to_string()]);
+        let mut queue = vec![name.to_string()];
+        while let Some(name) = queue.pop() {
+            let dependencies: Vec<&String> = if name == DEFAULT_SET {
+                self.sets.iter().map(|s| &s.name).collect()
+            } else {
+                self.sets
+                    .iter()
+                    .filter(|s| s.name == name)
+                    .flat_map(|s| &s.depends_on)
+                    .collect()
+            };
+            for dependency in dependencies {
+                if names.insert(dependency.clone()) {
+                    queue.push(dependency.clone());
+                }
+            }
+        }
+        names
+    }
+
+    fn apply(&mut self, scope: ApplyScope<'_>) -> Result<Vec<MigrationStatus>> {
+        let lock = self.lock()?;
+        self.ensure_tracking_table()?;
+
+        if let Some(e) = self.compatibility()?.into_error() {
+            if self.downgrade_policy == DowngradePolicy::Refuse {
+                return Err(e);
+            }
+            tracing::warn!(error = %e, "leaving database untouched for read-only use");
+            return self.status();
+        }
+
+        let included = match scope {
+            ApplyScope::Set(name) => Some(self.with_dependencies(name)),
+            ApplyScope::All | ApplyScope::UpTo(_) => None,
+        };
+        let mut pending: Vec<(&str, &Migration)> = Vec::new();
+        for set in self.sets()? {
+            if included
+                .as_ref()
+                .is_some_and(|names| !names.contains(&set.name))
+            {
+                continue;
+            }
+            let applied = self.get_applied(&set.name)?;
+            self.check_policy(set, &applied)?;
+            self.verify_checksums(set, &applied)?;
+            pending.extend(
+                set.migrations
+                    .iter()
+                    .filter(|m| !applied.contains_key(&m.version))
+                    .filter(|m| match scope {
+                        ApplyScope::UpTo(target) => set.name != DEFAULT_SET || m.version <= target,
+                        ApplyScope::All | ApplyScope::Set(_) => true,
+                    })
+                    .map(|m| (set.name.as_str(), m)),
+            );
+        }
+        if pending.is_empty() {
+            return self.status();
+        }
+
+        let backup = match &self.backup_dir {
+            Some(dir) => {
+                let history_id = history::last_id(&*self.pool.get()?)?;
+                let path = self.backup(dir)?;
+                self.prune_backups(dir);
+                Some((path, history_id))
+            }
+            None => None,
+        };
+
+        let total = pending.len();
+        for (i, (set, migration)) in pending.into_iter().enumerate() {
+            lock.heartbeat()?;
+            let (version, name, index) = (migration.version, migration.name.clone(), i + 1);
+            self.notify(MigrationProgress::Started {
+                set: set.to_string(),
+                version,
+                name: name.clone(),
+                index,
+                total,
+            });
+
+            let started = Instant::now();
+            if let Err(e) = self.apply_one(set, migration) {
+                let e = match backup {
+                    Some((path, history_id)) => self.restore_after_failure(e, path, history_id),
+                    None => e,
+                };
+                // Recorded after any restore so the backup does not erase it
+                self.record_failure(set, migration, MigrationEventKind::Apply, started, &e);
+                self.notify(MigrationProgress::Failed {
+                    set: set.to_string(),
+                    version,
+                    name,
+                    index,
+                    total,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+
+            self.notify(MigrationProgress::Finished {
+                set: set.to_string(),
+                version,
+                name,
+                index,
+                total,
+                duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            });
+        }
+
+        self.status()
+    }
+
+    fn notify(&self, progress: MigrationProgress) {
+        if let Some(observer) = &self.progress {
+            observer(&progress);
+        }
+    }
+
+    /// Write a consistent snapshot of the database into `dir`.
+    ///
+    /// Uses `VACUUM INTO`, so it is safe while other connections are open.
+    /// Returns the path of the new file, which gets a `_NNN` suffix if a backup
+    /// was already taken in the same millisecond.
+    pub fn backup(&self, dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        // `VACUUM INTO` refuses to overwrite. `_` sorts after `.`, so suffixed
+        // names stay in chronological order for pruning.
+        let mut path = dir.join(format!("pre-migration-{stamp}.db"));
+        let mut n = 0;
+        while path.exists() {
+            n += 1;
+            path = dir.join(format!("pre-migration-{stamp}_{n:03}.db"));
+        }
+
+        let conn = self.pool.get()?;
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        tracing::info!(path = %path.display(), "database backed up before migrating");
+        Ok(path)
+    }
+
+    /// Delete all but the newest [`Self::set_backups_to_keep`] snapshots in
+    /// `dir`. Failures are logged, since a leftover backup is harmless.
+    fn prune_backups(&self, dir: &Path) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!(dir = %dir.display(), error = %e, "failed to list backups");
+                return;
+            }
+        };
+        // Timestamps in the names sort chronologically
+        let mut backups: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("pre-migration-") && name.ends_with(".db"))
+            })
+            .collect();
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.backups_to_keep);
+        for path in &backups[..excess] {
+            match std::fs::remove_file(path) {
+                Ok(()) => tracing::info!(path = %path.display(), "deleted old backup"),
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "failed to delete old backup");
+                }
+            }
+        }
+    }
+
+    /// Copy a backup over the live database and wrap the original error.
+    ///
+    /// History events recorded after `history_id`, when the backup was taken,
+    /// are written back afterwards so the restore does not erase the log of
+    /// this run.
+    fn restore_after_failure(
+        &self,
+        error: ShipKitError,
+        backup_path: PathBuf,
+        history_id: i64,
+    ) -> ShipKitError {
+        let restored = self.pool.get().and_then(|mut conn| {
+            let events = history::load_since(&conn, history_id);
+            conn.restore(rusqlite::MAIN_DB, &backup_path, None::<fn(Progress)>)?;
+            if let Err(e) = events.and_then(|events| history::reinsert(&conn, &events)) {
+                tracing::warn!(error = %e, "failed to carry migration history over the restore");
+            }
+            Ok(())
+        });
+        match restored {
+            Ok(()) => {
+                tracing::warn!(
+                    path = %backup_path.display(),
+                    error = %error,
+                    "migration failed; database restored from backup"
+                );
+                ShipKitError::MigrationRestored {
+                    source: Box::new(error),
+                    backup_path,
+                }
+            }
+            Err(restore_error) => {
+                tracing::error!(
+                    path = %backup_path.display(),
+                    error = %error,
+                    restore_error = %restore_error,
+                    "migration failed and restoring the backup also failed"
+                );
+                ShipKitError::MigrationRestoreFailed {
+                    source: Box::new(error),
+                    backup_path,
+                    restore_error: Box::new(restore_error),
+                }
+            }
+        }
+    }
+
+    /// Make sure applied migrations still match their registered SQL.
+    fn verify_checksums(
+        &self,
+        set: &MigrationSet,
+        applied: &HashMap<i64, AppliedMigration>,
+    ) -> Result<()> {
+        for migration in &set.migrations {
+            let Some(existing) = applied.get(&migration.version) else {
+                continue;
+            };
+            let mode = ChecksumMode::from_algorithm(&existing.algorithm).ok_or_else(|| {
+                ShipKitError::Migration(format!(
+                    "unknown checksum algorithm '{}' for migration {}/{}",
+                    existing.algorithm, set.name, migration.version
+                ))
+            })?;
+            if existing.checksum != mode.checksum(&migration.up_sql) {
+                return Err(ShipKitError::Migration(format!(
+                    "checksum mismatch for migration {}/{}: {} \
+                     (call repair_checksums() if the edit was intentional)",
+                    set.name, migration.version, migration.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare applied history with registered migrations and reject any
+    /// drift the current policy does not allow.
+    fn check_policy(
+        &self,
+        set: &MigrationSet,
+        applied: &HashMap<i64, AppliedMigration>,
+    ) -> Result<()> {
+        let registered: Vec<i64> = set.migrations.iter().map(|m| m.version).collect();
+
+        if self.policy != MigrationPolicy::AllowOutOfOrder
+            && let Some(&latest_applied) = applied.keys().max()
+        {
+            let versions: Vec<i64> = registered
+                .iter()
+                .copied()
+                .filter(|v| *v < latest_applied && !applied.contains_key(v))
+                .collect();
+            if !versions.is_empty() {
+                return Err(ShipKitError::MigrationOutOfOrder {
+                    set: set.name.clone(),
+                    versions,
+                    latest_applied,
+                });
+            }
+        }
+
+        if self.policy != MigrationPolicy::IgnoreMissing {
+            let mut unregistered: Vec<i64> = applied
+                .keys()
+                .copied()
+                .filter(|v| !registered.contains(v))
+                .collect();
+            unregistered.sort_unstable();
+
+            let (first, last) = match (registered.first(), registered.last()) {
+                (Some(first), Some(last)) => (*first, *last),
+                _ => (i64::MAX, i64::MIN),
+            };
+            let (gaps, orphaned): (Vec<i64>, Vec<i64>) = unregistered
+                .into_iter()
+                .partition(|v| *v > first && *v < last);
+            if !gaps.is_empty() {
+                return Err(ShipKitError::MigrationGap {
+                    set: set.name.clone(),
+                    versions: gaps,
+                });
+            }
+            if !orphaned.is_empty() {
+                return Err(ShipKitError::MigrationOrphaned {
+                    set: set.name.clone(),
+                    versions: orphaned,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run a migration's up SQL and record it as applied.
+    fn apply_one(&self, set: &str, migration: &Migration) -> Result<()> {
+        let started = Instant::now();
+        let conn = self.pool.get()?;
+        let tx = run_script(&conn, migration, &migration.up_sql).map_err(|e| {
+            let message = if migration.is_no_transaction() {
+                "failed outside a transaction; statements before the failure were not rolled back"
+            } else {
+                "failed"
+            };
+            ShipKitError::Migration(format!(
+                "migration {} ({}) {message}: {e}",
+                migration.version, migration.name
+            ))
+        })?;
+        self.record_applied(&tx, set, migration)?;
+        history::record(
+            &tx,
+            self.event(set, migration, MigrationEventKind::Apply, started, None),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn record_applied(
+        &self,
+        conn: &rusqlite::Connection,
+        set: &str,
+        migration: &Migration,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT INTO _shipkit_migrations (\"set\", version, name, checksum, checksum_algorithm)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                set,
+                migration.version,
+                migration.name,
+                self.checksum_mode.checksum(&migration.up_sql),
+                self.checksum_mode.algorithm(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn event<'a>(
+        &'a self,
+        set: &'a str,
+        migration: &'a Migration,
+        kind: MigrationEventKind,
+        started: Instant,
+        error: Option<String>,
+    ) -> NewEvent<'a> {
+        NewEvent {
+            set,
+            version: migration.version,
+            name: &migration.name,
+            kind,
+            duration: started.elapsed(),
+            app_version: self.app_version.as_deref(),
+            checksum: self.checksum_mode.checksum(&migration.up_sql),
+            down_checksum: migration
+                .down_sql
+                .as_deref()
+                .map(|sql| self.checksum_mode.checksum(sql)),
+            error,
+        }
+    }
+
+    /// Log a failed attempt. Failing to write the log must not mask the
+    /// original error, so problems here are only traced.
+    fn record_failure(
+        &self,
+        set: &str,
+        migration: &Migration,
+        kind: MigrationEventKind,
+        started: Instant,
+        error: &ShipKitError,
+    ) {
+        let event = self.event(set, migration, kind, started, Some(error.to_string()));
+        let recorded = self
+            .pool
+            .get()
+            .and_then(|conn| history::record(&conn, event));
+        if let Err(e) = recorded {
+            tracing::warn!(
+                set,
+                version = migration.version,
+                error = %e,
+                "failed to record migration failure in history"
+            );
+        }
+    }
+
+    /// Rollback the most recently applied migration in the [`DEFAULT_SET`].
+    pub fn rollback_last(&mut self) -> Result<Option<MigrationStatus>> {
+        self.rollback_last_in(DEFAULT_SET)
+    }
+
+    /// Rollback the most recently applied migration in `set`.
+    ///
+    /// Refused with [`ShipKitError::SchemaTooNew`] whatever the
+    /// [`DowngradePolicy`] if a newer build has migrated the database.
+    pub fn rollback_last_in(&mut self, set: &str) -> Result<Option<MigrationStatus>> {
+        let _lock = self.lock()?;
+        self.ensure_tracking_table()?;
+        if let Some(e) = self.compatibility()?.into_error() {
+            return Err(e);
+        }
+        let applied = self.get_applied(set)?;
+
+        // Find the highest applied version
+        let last_version = applied.keys().max().copied();
+        let Some(last_version) = last_version else {
+            return Ok(None);
+        };
+
+        let migration = self
+            .sets
+            .iter()
+            .filter(|s| s.name == set)
+            .flat_map(|s| &s.migrations)
+            .find(|m| m.version == last_version)
+            .ok_or_else(|| ShipKitError::MigrationOrphaned {
+                set: set.to_string(),
+                versions: vec![last_version],
+            })?;
+
+        let down_sql = migration.down_sql.as_ref().ok_or_else(|| {
+            ShipKitError::Migration(format!(
+                "migration {} ({}) has no down SQL",
+                migration.version, migration.name
+            ))
+        })?;
+
+        let started = Instant::now();
+        let result = self.pool.get().and_then(|conn| {
+            let tx = run_script(&conn, migration, down_sql)?;
+            tx.execute(
+                "DELETE FROM _shipkit_migrations WHERE \"set\" = ?1 AND version = ?2",
+                rusqlite::params![set, last_version],
+            )?;
+            history::record(
+                &tx,
+                self.event(set, migration, MigrationEventKind::Rollback, started, None),
+            )?;
+            tx.commit()?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            self.record_failure(set, migration, MigrationEventKind::Rollback, started, &e);
+            return Err(e);
+        }
+
+        Ok(Some(MigrationStatus {
+            set: set.to_string(),
+            version: migration.version,
+            name: migration.name.clone(),
+            applied: false,
+            applied_at: None,
+            no_transaction: migration.is_no_transaction(),
+        }))
+    }
+
+    /// Rewrite stored checksums to match the registered migrations.
+    ///
+    /// Use after intentionally editing an applied migration (or switching
+    /// [`ChecksumMode`]). Every applied, registered migration whose stored
+    /// checksum or algorithm differs from the current one is updated and
+    /// logged. Returns the rows that changed.
+    pub fn repair_checksums(&mut self) -> Result<Vec<ChecksumRepair>> {
+        let _lock = self.lock()?;
+        self.ensure_tracking_table()?;
+        let applied: HashMap<&str, HashMap<i64, AppliedMigration>> = self
+            .sets
+            .iter()
+            .map(|s| Ok((s.name.as_str(), self.get_applied(&s.name)?)))
+            .collect::<Result<_>>()?;
+        let new_algorithm = self.checksum_mode.algorithm();
+
+        let conn = self.pool.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let mut repairs = Vec::new();
+        for (set, migration) in self
+            .sets
+            .iter()
+            .flat_map(|s| s.migrations.iter().map(move |m| (s.name.as_str(), m)))
+        {
+            let Some(existing) = applied.get(set).and_then(|a| a.get(&migration.version)) else {
+                continue;
+            };
+            let new_checksum = self.checksum_mode.checksum(&migration.up_sql);
+            if existing.checksum == new_checksum && existing.algorithm == new_algorithm {
+                continue;
+            }
+
+            tx.execute(
+                "UPDATE _shipkit_migrations SET checksum = ?1, checksum_algorithm = ?2
+                 WHERE \"set\" = ?3 AND version = ?4",
+                rusqlite::params![new_checksum, new_algorithm, set, migration.version],
+            )?;
+            tracing::warn!(
+                set,
+                version = migration.version,
+                name = %migration.name,
+                old_checksum = %existing.checksum,
+                old_algorithm = %existing.algorithm,
+                new_checksum = %new_checksum,
+                new_algorithm,
+                "repaired migration checksum"
+            );
+            repairs.push(ChecksumRepair {
+                set: set.to_string(),
+                version: migration.version,
+                name: migration.name.clone(),
+                old_checksum: existing.checksum.clone(),
+                old_algorithm: existing.algorithm.clone(),
+                new_checksum,
+                new_algorithm: new_algorithm.to_string(),
+            });
+        }
+        tx.commit()?;
+
+        Ok(repairs)
+    }
+
+    /// Mark every migration in the [`DEFAULT_SET`] up to and including
+    /// `version` as applied without running it.
+    ///
+    /// For databases that were created by hand before adopting ShipKit.
+    /// Checksums are recorded as if the migrations had run, and each one gets
+    /// a `baseline` entry in the history. Migrations that are already applied
+    /// are left alone.
+    pub fn baseline(&mut self, version: i64) -> Result<Vec<MigrationStatus>> {
+        self.run_baseline(version, false)
+    }
+
+    /// Like [`baseline`](Self::baseline), but first check that every table the
+    /// baselined migrations would have created already exists, failing with
+    /// [`ShipKitError::BaselineMismatch`] otherwise.
+    pub fn baseline_verified(&mut self, version: i64) -> Result<Vec<MigrationStatus>> {
+        self.run_baseline(version, true)
+    }
+
+    fn run_baseline(&mut self, version: i64, verify: bool) -> Result<Vec<MigrationStatus>> {
+        let _lock = self.lock()?;
+        self.ensure_tracking_table()?;
+        let applied = self.get_applied(DEFAULT_SET)?;
+
+        let targets: Vec<&Migration> = self
+            .migrations()
+            .iter()
+            .filter(|m| m.version <= version)
+            .collect();
+        if targets.is_empty() {
+            return Err(ShipKitError::Migration(format!(
+                "cannot baseline at version {version}: no migrations registered at or below it"
+            )));
+        }
+
+        let conn = self.pool.get()?;
+        if verify {
+            let mut expected = BTreeSet::new();
+            for migration in &targets {
+                schema::apply_table_changes(&migration.up_sql, &mut expected);
+            }
+            let existing = schema::table_names(&conn)?;
+            let missing_tables: Vec<String> = expected.difference(&existing).cloned().collect();
+            if !missing_tables.is_empty() {
+                return Err(ShipKitError::BaselineMismatch {
+                    version,
+                    missing_tables,
+                });
+            }
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        for migration in targets {
+            if applied.contains_key(&migration.version) {
+                continue;
+            }
+            let started = Instant::now();
+            self.record_applied(&tx, DEFAULT_SET, migration)?;
+            history::record(
+                &tx,
+                self.event(
+                    DEFAULT_SET,
+                    migration,
+                    MigrationEventKind::Baseline,
+                    started,
+                    None,
+                ),
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        tracing::info!(version, "baselined existing database");
+        self.status()
+    }
+
+    /// Check whether a newer build has applied migrations this engine does not
+    /// know about: for each registered set, the highest applied version is
+    /// compared with the highest registered one.
+    pub fn compatibility(&self) -> Result<SchemaCompatibility> {
+        self.ensure_tracking_table()?;
+        for set in self.sets()? {
+            let Some(supported_version) = set.migrations.iter().map(|m| m.version).max() else {
+                continue;
+            };
+            let database_version = self.get_applied(&set.name)?.into_keys().max();
+            if let Some(database_version) = database_version
+                && database_version > supported_version
+            {
+                return Ok(SchemaCompatibility::Newer {
+                    set: set.name.clone(),
+                    database_version,
+                    supported_version,
+                });
+            }
+        }
+        Ok(SchemaCompatibility::Compatible)
+    }
+
+    /// Every apply and rollback attempt recorded so far, oldest first.
+    pub fn history(&self) -> Result<Vec<MigrationEvent>> {
+        self.ensure_tracking_table()?;
+        let conn = self.pool.get()?;
+        history::load(&conn)
+    }
+
+    /// Get the status of all registered migrations, set by set in the order
+    /// they are applied.
+    pub fn status(&self) -> Result<Vec<MigrationStatus>> {
+        self.ensure_tracking_table()?;
+        let sets = self.sets()?;
+        let conn = self.pool.get()?;
+        let mut stmt =
+            conn.prepare("SELECT \"set\", version, applied_at FROM _shipkit_migrations")?;
+        let applied: HashMap<(String, i64), String> = stmt
+            .query_map([], |row| Ok(((row.get(0)?, row.get(1)?), row.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(sets
+            .into_iter()
+            .flat_map(|set| {
+                set.migrations.iter().map(|m| {
+                    let applied_at = applied.get(&(set.name.clone(), m.version)).cloned();
+                    MigrationStatus {
+                        set: set.name.clone(),
+                        version: m.version,
+                        name: m.name.clone(),
+                        applied: applied_at.is_some(),
+                        applied_at,
+                        no_transaction: m.is_no_transaction(),
+                    }
+                })
+            })
+            .collect())
+    }
+
+    fn ensure_tracking_table(&self) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS _shipkit_migrations ({TRACKING_COLUMNS});"
+        ))?;
+        // Tables created before checksum modes existed
+        add_column_if_missing(
+            &conn,
+            "_shipkit_migrations",
+            "checksum_algorithm",
+            "TEXT NOT NULL DEFAULT 'sha256'",
+        )?;
+        // Tables created before migration sets were keyed by version alone,
+        // so the primary key changes and the table has to be rebuilt
+        if !has_column(&conn, "_shipkit_migrations", "set")? {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute_batch(&format!(
+                "CREATE TABLE _shipkit_migrations_new ({TRACKING_COLUMNS});
+                 INSERT INTO _shipkit_migrations_new
+                     (\"set\", version, name, checksum, applied_at, checksum_algorithm)
+                 SELECT '{DEFAULT_SET}', version, name, checksum, applied_at, checksum_algorithm
+                 FROM _shipkit_migrations;
+                 DROP TABLE _shipkit_migrations;
+                 ALTER TABLE _shipkit_migrations_new RENAME TO _shipkit_migrations;"
+            ))?;
+            tx.commit()?;
+        }
+        history::ensure_history_table(&conn)?;
+        lock::ensure_lock_table(&conn)
+    }
+
+    /// Take the migration lock. Anything that changes the tracking tables,
+    /// including creating or upgrading them, happens while it is held.
+    fn lock(&self) -> Result<MigrationLock<'_>> {
+        lock::ensure_lock_table(&*self.pool.get()?)?;
+        MigrationLock::acquire(&self.pool, self.lock_timeout, self.stale_lock_after)
+    }
+
+    /// Applied migrations of one set, by version.
+    fn get_applied(&self, set: &str) -> Result<HashMap<i64, AppliedMigration>> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT version, checksum, checksum_algorithm FROM _shipkit_migrations
+             WHERE \"set\" = ?1",
+        )?;
+        let map: HashMap<i64, AppliedMigration> = stmt
+            .query_map([set], |row| {
+                Ok((
+                    row.get(0)?,
+                    AppliedMigration {
+                        checksum: row.get(1)?,
+                        algorithm: row.get(2)?,
+                    },
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(map)
+    }
+}
+
+fn has_column(conn: &rusqlite::Connection, table: &str, column: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        rusqlite::params![table, column],
+        |row| row.get(0),
+    )?)
+}
+
+/// Add a column to an existing table unless it is already there.
+pub(crate) fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {definition};",
+            quote_ident(table),
+            quote_ident(column)
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn test_pool() -> ConnectionPool {
+        ConnectionPool::in_memory().expect("in-memory pool")
+    }
+
+    #[test]
+    fn apply_single_migration() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.register(Migration {
+            version: 1,
+            name: "create_users".into(),
+            up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);".into(),
+            down_sql: Some("DROP TABLE users;".into()),
+        });
+
+        let statuses = engine.apply_pending().expect("apply");
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].applied);
+
+        // Verify table exists
+        let conn = pool.get().expect("conn");
+        conn.execute("INSERT INTO users (name) VALUES ('test')", [])
+            .expect("insert into created table");
+    }
+
+    #[test]
+    fn apply_multiple_migrations() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_users".into(),
+                up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY);".into(),
+                down_sql: Some("DROP TABLE users;".into()),
+            })
+            .register(Migration {
+                version: 2,
+                name: "create_posts".into(),
+                up_sql: "CREATE TABLE posts (id INTEGER PRIMARY KEY);".into(),
+                down_sql: Some("DROP TABLE posts;".into()),
+            })
+            .register(Migration {
+                version: 3,
+                name: "create_comments".into(),
+                up_sql: "CREATE TABLE comments (id INTEGER PRIMARY KEY);".into(),
+                down_sql: Some("DROP TABLE comments;".into()),
+            });
+
+        let statuses = engine.apply_pending().expect("apply");
+        assert_eq!(statuses.len(), 3);
+        assert!(statuses.iter().all(|s| s.applied));
+    }
+
+    #[test]
+    fn idempotent_apply() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+
+        engine.apply_pending().expect("first apply");
+        let statuses = engine.apply_pending().expect("second apply");
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].applied);
+    }
+
+    #[test]
+    fn rollback_last() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_a".into(),
+                up_sql: "CREATE TABLE a (id INTEGER PRIMARY KEY);".into(),
+                down_sql: Some("DROP TABLE a;".into()),
+            })
+            .register(Migration {
+                version: 2,
+                name: "create_b".into(),
+                up_sql: "CREATE TABLE b (id INTEGER PRIMARY KEY);".into(),
+                down_sql: Some("DROP TABLE b;".into()),
+            });
+
+        engine.apply_pending().expect("apply");
+
+        let rolled_back = engine.rollback_last().expect("rollback");
+        assert!(rolled_back.is_some());
+        assert_eq!(rolled_back.as_ref().map(|s| s.version), Some(2));
+
+        let statuses = engine.status().expect("status");
+        assert!(statuses[0].applied);
+        assert!(!statuses[1].applied);
+
+        // Verify table b is gone
+        let conn = pool.get().expect("conn");
+        let err = conn.execute("INSERT INTO b (id) VALUES (1)", []);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn rollback_without_down_sql() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+
+        engine.apply_pending().expect("apply");
+
+        let result = engine.rollback_last();
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("no down SQL"));
+    }
+
+    #[test]
+    fn transaction_rollback_on_bad_sql() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "bad_migration".into(),
+            up_sql: "THIS IS NOT VALID SQL;".into(),
+            down_sql: None,
+        });
+
+        let result = engine.apply_pending();
+        assert!(result.is_err());
+
+        // Verify migration was NOT recorded
+        let statuses = engine.status().expect("status");
+        assert!(!statuses[0].applied);
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("first apply");
+
+        // Now register same version with different SQL
+        engine.sets.clear();
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);".into(),
+            down_sql: None,
+        });
+
+        let result = engine.apply_pending();
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn file_based_loading() {
+        let tmp = TempDir::new().expect("tmp dir");
+
+        std::fs::write(
+            tmp.path().join("001_create_users.sql"),
+            "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        )
+        .expect("write file");
+
+        std::fs::write(
+            tmp.path().join("002_create_posts.sql"),
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY);",
+        )
+        .expect("write file");
+
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register_from_dir(tmp.path()).expect("load dir");
+
+        assert_eq!(engine.migrations().len(), 2);
+        assert_eq!(engine.migrations()[0].version, 1);
+        assert_eq!(engine.migrations()[0].name, "create_users");
+        assert_eq!(engine.migrations()[1].version, 2);
+    }
+
+    #[test]
+    fn file_based_with_down_section() {
+        let tmp = TempDir::new().expect("tmp dir");
+
+        std::fs::write(
+            tmp.path().join("001_create_users.sql"),
+            "CREATE TABLE users (id INTEGER PRIMARY KEY);\n-- DOWN\nDROP TABLE users;",
+        )
+        .expect("write file");
+
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register_from_dir(tmp.path()).expect("load dir");
+
+        assert_eq!(engine.migrations().len(), 1);
+        assert!(engine.migrations()[0].down_sql.is_some());
+        assert_eq!(
+            engine.migrations()[0].down_sql.as_deref(),
+            Some("DROP TABLE users;")
+        );
+    }
+
+    #[test]
+    fn no_transaction_migration_runs_vacuum() {
+        let tmp = TempDir::new().expect("tmp dir");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_t".into(),
+                up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+                down_sql: None,
+            })
+            .register(Migration::new(2, "vacuum", "VACUUM;").no_transaction());
+
+        let statuses = engine.apply_pending().expect("apply");
+        assert!(statuses.iter().all(|s| s.applied));
+        assert!(statuses[1].no_transaction);
+    }
+
+    #[test]
+    fn failed_no_transaction_migration_is_not_recorded() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.register(
+            Migration::new(
+                1,
+                "partial",
+                "PRAGMA foreign_keys=OFF; BEGIN; CREATE TABLE t (id INTEGER PRIMARY KEY); NOT VALID SQL;",
+            )
+            .no_transaction(),
+        );
+
+        let err = engine.apply_pending().expect_err("invalid SQL").to_string();
+        assert!(err.contains("outside a transaction"));
+        assert!(!engine.status().expect("status")[0].applied);
+
+        // The connection goes back to the pool clean
+        let conn = pool.get().expect("conn");
+        assert!(conn.is_autocommit());
+        let fk: i32 = conn
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .expect("query foreign_keys");
+        assert_eq!(fk, 1);
+    }
+
+    #[test]
+    fn no_transaction_migrations_ignore_unrelated_foreign_key_violations() {
+        let pool = test_pool();
+        {
+            let conn = pool.get().expect("conn");
+            conn.execute_batch(
+                "PRAGMA foreign_keys=OFF;
+                 CREATE TABLE users (id INTEGER PRIMARY KEY);
+                 CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+                 INSERT INTO posts (id, user_id) VALUES (1, 99);
+                 CREATE TABLE tags (id INTEGER PRIMARY KEY, label TEXT);",
+            )
+            .expect("dangling row");
+        }
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine
+            .register(Migration::new(1, "vacuum", "VACUUM;").no_transaction())
+            .register(Migration::rebuild_table(
+                2,
+                "tags_label_not_null",
+                TableRebuild {
+                    table: "tags",
+                    definition: "id INTEGER PRIMARY KEY, label TEXT NOT NULL",
+                    columns: &["id", "label"],
+                    after_sql: "",
+                },
+            ));
+
+        engine.apply_pending().expect("apply");
+        assert!(engine.status().expect("status").iter().all(|s| s.applied));
+
+        // The connection keeps the foreign_keys setting it had before
+        let fk: i32 = pool
+            .get()
+            .expect("conn")
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .expect("query foreign_keys");
+        assert_eq!(fk, 0);
+    }
+
+    #[test]
+    fn rebuild_table_rolls_back_with_a_rebuild() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        let nullable_name = TableRebuild {
+            table: "users",
+            definition: "id INTEGER PRIMARY KEY, name TEXT",
+            columns: &["id", "name"],
+            after_sql: "",
+        };
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_tables".into(),
+                up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+                         CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+                         INSERT INTO users (id, name) VALUES (1, 'a');
+                         INSERT INTO posts (id, user_id) VALUES (1, 1);"
+                    .into(),
+                down_sql: None,
+            })
+            .register(Migration {
+                down_sql: Some(nullable_name.to_sql()),
+                ..Migration::rebuild_table(
+                    2,
+                    "users_name_not_null",
+                    TableRebuild {
+                        definition: "id INTEGER PRIMARY KEY, name TEXT NOT NULL",
+                        ..nullable_name
+                    },
+                )
+            });
+        engine.apply_pending().expect("apply");
+        // Verification runs the down script the way the rollback below does
+        let reports = engine.verify_reversible().expect("verify");
+        assert!(reports[1].is_reversible(), "{:?}", reports[1].outcome);
+
+        let rolled_back = engine
+            .rollback_last()
+            .expect("rollback")
+            .expect("a migration");
+        assert_eq!(rolled_back.version, 2);
+        assert!(!engine.status().expect("status")[1].applied);
+
+        let conn = pool.get().expect("conn");
+        conn.execute("INSERT INTO users (id) VALUES (2)", [])
+            .expect("name is nullable again");
+        let posts: i64 = conn
+            .query_row("SELECT count(*) FROM posts WHERE user_id = 1", [], |r| {
+                r.get(0)
+            })
+            .expect("posts");
+        assert_eq!(posts, 1);
+        assert!(conn.is_autocommit());
+        let fk: i32 = conn
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .expect("query foreign_keys");
+        assert_eq!(fk, 1);
+    }
+
+    #[test]
+    fn rebuild_table_preserves_rows() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_tables".into(),
+                up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+                         CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+                         INSERT INTO users (id, name) VALUES (1, 'a');
+                         INSERT INTO posts (id, user_id) VALUES (1, 1);"
+                    .into(),
+                down_sql: None,
+            })
+            .register(Migration::rebuild_table(
+                2,
+                "users_name_not_null",
+                TableRebuild {
+                    table: "users",
+                    definition: "id INTEGER PRIMARY KEY, name TEXT NOT NULL",
+                    columns: &["id", "name"],
+                    after_sql: "CREATE INDEX idx_users_name ON users(name);",
+                },
+            ));
+
+        let statuses = engine.apply_pending().expect("apply");
+        assert!(statuses.iter().all(|s| s.applied));
+
+        let conn = pool.get().expect("conn");
+        let name: String = conn
+            .query_row("SELECT name FROM users WHERE id = 1", [], |row| row.get(0))
+            .expect("row survived rebuild");
+        assert_eq!(name, "a");
+        let err = conn.execute("INSERT INTO users (id) VALUES (2)", []);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn rebuild_table_with_dangling_reference_is_not_recorded() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_tables".into(),
+                up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY);
+                         CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+                         INSERT INTO users (id) VALUES (1);
+                         INSERT INTO posts (id, user_id) VALUES (1, 1);"
+                    .into(),
+                down_sql: None,
+            })
+            .register(Migration::rebuild_table(
+                2,
+                "drop_user_rows",
+                TableRebuild {
+                    table: "users",
+                    definition: "id INTEGER PRIMARY KEY",
+                    columns: &["id"],
+                    after_sql: "DELETE FROM users;",
+                },
+            ));
+
+        let err = engine
+            .apply_pending()
+            .expect_err("posts would dangle")
+            .to_string();
+        assert!(err.contains("foreign key violation"));
+        assert!(!engine.status().expect("status")[1].applied);
+
+        let conn = engine.pool.get().expect("conn");
+        let users: i64 = conn
+            .query_row("SELECT count(*) FROM users", [], |r| r.get(0))
+            .expect("old users table");
+        assert_eq!(users, 1);
+        let leftovers: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM sqlite_master WHERE name = '_shipkit_new_users'",
+                [],
+                |r| r.get(0),
+            )
+            .expect("sqlite_master");
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn file_based_no_transaction_directive() {
+        let tmp = TempDir::new().expect("tmp dir");
+
+        std::fs::write(
+            tmp.path().join("001_vacuum.sql"),
+            "-- shipkit:no-transaction\nVACUUM;",
+        )
+        .expect("write file");
+
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register_from_dir(tmp.path()).expect("load dir");
+
+        assert!(engine.migrations()[0].is_no_transaction());
+    }
+
+    #[test]
+    fn normalized_checksum_ignores_whitespace_and_comments() {
+        let a = "CREATE TABLE t (\n    id INTEGER PRIMARY KEY -- key\n);\n";
+        let b = "/* tidy */ CREATE TABLE t (\r\n  id INTEGER PRIMARY KEY\r\n);";
+        assert_eq!(
+            ChecksumMode::Normalized.checksum(a),
+            ChecksumMode::Normalized.checksum(b)
+        );
+        assert_ne!(
+            ChecksumMode::Exact.checksum(a),
+            ChecksumMode::Exact.checksum(b)
+        );
+    }
+
+    #[test]
+    fn normalized_checksum_keeps_string_literals() {
+        let a = "INSERT INTO t VALUES ('a  -- b');";
+        let b = "INSERT INTO t VALUES ('a -- b');";
+        assert_ne!(
+            ChecksumMode::Normalized.checksum(a),
+            ChecksumMode::Normalized.checksum(b)
+        );
+    }
+
+    #[test]
+    fn normalized_mode_tolerates_reformatting() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_checksum_mode(ChecksumMode::Normalized);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("first apply");
+
+        engine.sets.clear();
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "-- users table\r\nCREATE TABLE t (\r\n    id INTEGER PRIMARY KEY\r\n);\r\n"
+                .into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("reformatted apply");
+    }
+
+    #[test]
+    fn rows_are_verified_with_their_stored_algorithm() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("exact apply");
+
+        // Switching modes does not invalidate rows recorded under the old one
+        engine.set_checksum_mode(ChecksumMode::Normalized);
+        engine.apply_pending().expect("normalized apply");
+    }
+
+    #[test]
+    fn repair_checksums_after_edit() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("first apply");
+
+        engine.sets.clear();
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY); -- edited".into(),
+            down_sql: None,
+        });
+        assert!(engine.apply_pending().is_err());
+
+        let repairs = engine.repair_checksums().expect("repair");
+        assert_eq!(repairs.len(), 1);
+        assert_eq!(repairs[0].version, 1);
+        assert_ne!(repairs[0].old_checksum, repairs[0].new_checksum);
+
+        engine.apply_pending().expect("apply after repair");
+        assert!(engine.repair_checksums().expect("repair again").is_empty());
+    }
+
+    #[test]
+    fn repair_checksums_switches_algorithm() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("apply");
+
+        engine.set_checksum_mode(ChecksumMode::Normalized);
+        let repairs = engine.repair_checksums().expect("repair");
+        assert_eq!(repairs.len(), 1);
+        assert_eq!(repairs[0].old_algorithm, "sha256");
+        assert_eq!(repairs[0].new_algorithm, "sha256-normalized");
+    }
+
+    #[test]
+    fn legacy_tracking_table_is_upgraded() {
+        let pool = test_pool();
+        {
+            let conn = pool.get().expect("conn");
+            conn.execute_batch(
+                "CREATE TABLE _shipkit_migrations (
+                    version INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    checksum TEXT NOT NULL,
+                    applied_at TEXT NOT NULL DEFAULT (datetime('now'))
+                );",
+            )
+            .expect("legacy table");
+            conn.execute(
+                "INSERT INTO _shipkit_migrations (version, name, checksum) VALUES (1, 'create_t', ?1)",
+                [ChecksumMode::Exact.checksum("CREATE TABLE t (id INTEGER PRIMARY KEY);")],
+            )
+            .expect("legacy row");
+        }
+
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(Migration {
+            version: 1,
+            name: "create_t".into(),
+            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+        let statuses = engine.apply_pending().expect("apply");
+        assert!(statuses[0].applied);
+        assert_eq!(statuses[0].set, DEFAULT_SET);
+    }
+
+    fn table_migration(version: i64, table: &str) -> Migration {
+        Migration {
+            version,
+            name: format!("create_{table}"),
+            up_sql: format!("CREATE TABLE {table} (id INTEGER PRIMARY KEY);"),
+            down_sql: Some(format!("DROP TABLE {table};")),
+        }
+    }
+
+    #[test]
+    fn strict_policy_rejects_out_of_order() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(table_migration(1, "a"))
+            .register(table_migration(5, "e"));
+        engine.apply_pending().expect("apply");
+
+        engine.register(table_migration(3, "c"));
+        let err = engine.apply_pending().expect_err("3 is older than 5");
+        assert!(matches!(
+            err,
+            ShipKitError::MigrationOutOfOrder { ref versions, latest_applied: 5, .. } if versions == &[3]
+        ));
+        assert!(!engine.status().expect("status")[1].applied);
+    }
+
+    #[test]
+    fn allow_out_of_order_policy_applies_late_migration() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_policy(MigrationPolicy::AllowOutOfOrder);
+        engine
+            .register(table_migration(1, "a"))
+            .register(table_migration(5, "e"));
+        engine.apply_pending().expect("apply");
+
+        engine.register(table_migration(3, "c"));
+        let statuses = engine.apply_pending().expect("apply out of order");
+        assert!(statuses.iter().all(|s| s.applied));
+    }
+
+    #[test]
+    fn strict_policy_rejects_gap() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(table_migration(1, "a"))
+            .register(table_migration(2, "b"))
+            .register(table_migration(3, "c"));
+        engine.apply_pending().expect("apply");
+
+        engine
+            .set_mut(DEFAULT_SET)
+            .migrations
+            .retain(|m| m.version != 2);
+        let err = engine.apply_pending().expect_err("2 is missing");
+        assert!(matches!(err, ShipKitError::MigrationGap { ref versions, .. } if versions == &[2]));
+    }
+
+    #[test]
+    fn strict_policy_rejects_orphaned() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(table_migration(1, "a"))
+            .register(table_migration(2, "b"));
+        engine.apply_pending().expect("apply");
+
+        engine
+            .set_mut(DEFAULT_SET)
+            .migrations
+            .retain(|m| m.version != 1);
+        let err = engine.apply_pending().expect_err("1 is not registered");
+        assert!(
+            matches!(err, ShipKitError::MigrationOrphaned { ref versions, .. } if versions == &[1])
+        );
+    }
+
+    #[test]
+    fn ignore_missing_policy_tolerates_unregistered() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_policy(MigrationPolicy::IgnoreMissing);
+        engine
+            .register(table_migration(1, "a"))
+            .register(table_migration(2, "b"))
+            .register(table_migration(3, "c"));
+        engine.apply_pending().expect("apply");
+
+        engine
+            .set_mut(DEFAULT_SET)
+            .migrations
+            .retain(|m| m.version == 3);
+        engine.register(table_migration(4, "d"));
+        let statuses = engine.apply_pending().expect("apply with missing");
+        assert!(statuses.iter().all(|s| s.applied));
+    }
+
+    #[test]
+    fn failed_migration_restores_backup() {
+        let tmp = TempDir::new().expect("tmp dir");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.set_backup_dir(tmp.path().join("backups"));
+        engine.register(Migration {
+            version: 1,
+            name: "create_notes".into(),
+            up_sql: "CREATE TABLE notes (id INTEGER PRIMARY KEY);
+                     INSERT INTO notes (id) VALUES (1);"
+                .into(),
+            down_sql: None,
+        });
+        engine.apply_pending().expect("apply");
+
+        // Runs outside a transaction, so the DROP would stick without the backup
+        engine.register(
+            Migration::new(2, "broken", "DROP TABLE notes; NOT VALID SQL;").no_transaction(),
+        );
+        let err = engine.apply_pending().expect_err("migration 2 fails");
+        assert!(
+            matches!(&err, ShipKitError::MigrationRestored { backup_path, .. } if backup_path.exists()),
+            "{err}"
+        );
+
+        let conn = pool.get().expect("conn");
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+            .expect("notes table restored");
+        assert_eq!(count, 1);
+        drop(conn);
+        assert!(!engine.status().expect("status")[1].applied);
+    }
+
+    #[test]
+    fn no_backup_when_nothing_is_pending() {
+        let tmp = TempDir::new().expect("tmp dir");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
         let mut engine = MigrationEngine::new(pool);
-        engine.register(Migration {
-            version: 1,
-            name: "create_t".into(),
-            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
-            down_sql: None,
-        });
+        engine.set_backup_dir(tmp.path().join("backups"));
+        engine.register(table_migration(1, "a"));
+
+        engine.apply_pending().expect("first apply");
+        engine.apply_pending().expect("second apply");
+
+        let backups = std::fs::read_dir(tmp.path().join("backups"))
+            .expect("backup dir")
+            .count();
+        assert_eq!(backups, 1);
+    }
+
+    #[test]
+    fn backups_in_the_same_millisecond_get_distinct_names() {
+        let tmp = TempDir::new().expect("tmp dir");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
+        let engine = MigrationEngine::new(pool);
+
+        let paths: Vec<PathBuf> = (0..5)
+            .map(|_| engine.backup(tmp.path().join("backups")).expect("backup"))
+            .collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(sorted, paths);
+        sorted.dedup();
+        assert_eq!(sorted.len(), 5);
+        assert!(paths.iter().all(|path| path.exists()));
+    }
+
+    #[test]
+    fn only_the_newest_backups_are_kept() {
+        let tmp = TempDir::new().expect("tmp dir");
+        let dir = tmp.path().join("backups");
+        std::fs::create_dir_all(&dir).expect("backup dir");
+        std::fs::write(dir.join("notes.txt"), "not a backup").expect("write");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_backup_dir(&dir).set_backups_to_keep(2);
+
+        let mut taken = Vec::new();
+        for version in 1..=3 {
+            engine.register(table_migration(version, &format!("t{version}")));
+            engine.apply_pending().expect("apply");
+            let mut names: Vec<String> = std::fs::read_dir(&dir)
+                .expect("read dir")
+                .map(|e| e.expect("entry").file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("pre-migration-"))
+                .collect();
+            names.sort();
+            taken.push(names.last().cloned().expect("new backup"));
+        }
+
+        let mut remaining: Vec<String> = std::fs::read_dir(&dir)
+            .expect("read dir")
+            .map(|e| e.expect("entry").file_name().to_string_lossy().into_owned())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            ["notes.txt", taken[1].as_str(), taken[2].as_str()]
+        );
+    }
+
+    fn hold_lock(pool: &ConnectionPool, owner: &str, age_secs: i64) {
+        let engine = MigrationEngine::new(pool.clone());
+        engine.ensure_tracking_table().expect("tracking table");
+        let conn = pool.get().expect("conn");
+        conn.execute(
+            "INSERT INTO _shipkit_migration_lock (id, owner, acquired_at)
+             VALUES (1, ?1, CAST(strftime('%s', 'now') AS INTEGER) - ?2)",
+            rusqlite::params![owner, age_secs],
+        )
+        .expect("insert lock row");
+    }
+
+    #[test]
+    fn heartbeat_refreshes_the_lock() {
+        let pool = test_pool();
+        let engine = MigrationEngine::new(pool.clone());
+        engine.ensure_tracking_table().expect("tracking table");
+        let lock = engine.lock().expect("lock");
+        let execute = |sql: &str| {
+            pool.get().expect("conn").execute(sql, []).expect("execute");
+        };
+
+        // As if the run had been going for an hour
+        execute("UPDATE _shipkit_migration_lock SET acquired_at = acquired_at - 3600");
+        lock.heartbeat().expect("heartbeat");
+        let age: i64 = pool
+            .get()
+            .expect("conn")
+            .query_row(
+                "SELECT CAST(strftime('%s', 'now') AS INTEGER) - acquired_at
+                 FROM _shipkit_migration_lock",
+                [],
+                |row| row.get(0),
+            )
+            .expect("lock age");
+        assert!(age < 60);
 
+        // Broken by another process
+        execute("DELETE FROM _shipkit_migration_lock");
+        assert!(lock.heartbeat().is_err());
+    }
+
+    #[test]
+    fn lock_is_released_after_apply() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.register(table_migration(1, "a"));
         engine.apply_pending().expect("apply");
 
-        let result = engine.rollback_last();
-        assert!(result.is_err());
-        let err = result.unwrap_err().to_string();
-        assert!(err.contains("no down SQL"));
+        let conn = pool.get().expect("conn");
+        let held: i64 = conn
+            .query_row("SELECT COUNT(*) FROM _shipkit_migration_lock", [], |row| {
+                row.get(0)
+            })
+            .expect("count locks");
+        assert_eq!(held, 0);
     }
 
     #[test]
-    fn transaction_rollback_on_bad_sql() {
+    fn held_lock_times_out() {
         let pool = test_pool();
+        hold_lock(&pool, "other process", 0);
+
         let mut engine = MigrationEngine::new(pool);
-        engine.register(Migration {
-            version: 1,
-            name: "bad_migration".into(),
-            up_sql: "THIS IS NOT VALID SQL;".into(),
-            down_sql: None,
-        });
+        engine.set_lock_timeout(Duration::from_millis(200));
+        engine.register(table_migration(1, "a"));
 
-        let result = engine.apply_pending();
-        assert!(result.is_err());
+        let err = engine.apply_pending().expect_err("lock is held");
+        assert!(
+            matches!(err, ShipKitError::MigrationLocked { ref owner, .. } if owner == "other process")
+        );
+        assert!(!engine.status().expect("status")[0].applied);
+    }
 
-        // Verify migration was NOT recorded
-        let statuses = engine.status().expect("status");
-        assert!(!statuses[0].applied);
+    #[test]
+    fn tracking_table_is_not_upgraded_without_the_lock() {
+        let pool = test_pool();
+        {
+            let conn = pool.get().expect("conn");
+            conn.execute_batch(
+                "CREATE TABLE _shipkit_migrations (
+                    version INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    checksum TEXT NOT NULL,
+                    applied_at TEXT NOT NULL DEFAULT (datetime('now'))
+                );",
+            )
+            .expect("legacy table");
+            lock::ensure_lock_table(&conn).expect("lock table");
+            conn.execute(
+                "INSERT INTO _shipkit_migration_lock (id, owner, acquired_at)
+                 VALUES (1, 'other process', CAST(strftime('%s', 'now') AS INTEGER))",
+                [],
+            )
+            .expect("insert lock row");
+        }
+
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.set_lock_timeout(Duration::from_millis(200));
+        engine.register(table_migration(1, "a"));
+
+        let err = engine.apply_pending().expect_err("lock is held");
+        assert!(matches!(err, ShipKitError::MigrationLocked { .. }));
+        let conn = pool.get().expect("conn");
+        assert!(!has_column(&conn, "_shipkit_migrations", "set").expect("columns"));
     }
 
     #[test]
-    fn checksum_mismatch() {
+    fn stale_lock_is_broken() {
+        let pool = test_pool();
+        hold_lock(&pool, "crashed process", 3600);
+
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .set_lock_timeout(Duration::from_millis(200))
+            .set_stale_lock_after(Duration::from_secs(60));
+        engine.register(table_migration(1, "a"));
+
+        let statuses = engine.apply_pending().expect("apply after breaking lock");
+        assert!(statuses[0].applied);
+    }
+
+    #[test]
+    fn history_records_apply_and_rollback() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_app_version("1.2.3");
+        engine.register(table_migration(1, "a"));
+
+        engine.apply_pending().expect("apply");
+        engine.rollback_last().expect("rollback");
+        engine.apply_pending().expect("re-apply");
+
+        let history = engine.history().expect("history");
+        let kinds: Vec<_> = history.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                MigrationEventKind::Apply,
+                MigrationEventKind::Rollback,
+                MigrationEventKind::Apply
+            ]
+        );
+        assert!(history.iter().all(|e| e.success));
+        assert_eq!(history[0].app_version.as_deref(), Some("1.2.3"));
+        assert!(history[1].down_checksum.is_some());
+    }
+
+    #[test]
+    fn history_records_failures() {
         let pool = test_pool();
         let mut engine = MigrationEngine::new(pool);
         engine.register(Migration {
             version: 1,
-            name: "create_t".into(),
-            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY);".into(),
+            name: "bad_migration".into(),
+            up_sql: "THIS IS NOT VALID SQL;".into(),
             down_sql: None,
         });
-        engine.apply_pending().expect("first apply");
+        assert!(engine.apply_pending().is_err());
+
+        let history = engine.history().expect("history");
+        assert_eq!(history.len(), 1);
+        assert!(!history[0].success);
+        let error = history[0].error.as_deref().unwrap_or_default();
+        assert!(error.contains("bad_migration"));
+    }
+
+    #[test]
+    fn history_survives_backup_restore() {
+        let tmp = TempDir::new().expect("tmp dir");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_backup_dir(tmp.path().join("backups"));
+        engine.register(table_migration(1, "a"));
+        engine.apply_pending().expect("apply");
 
-        // Now register same version with different SQL
-        engine.migrations.clear();
         engine.register(Migration {
-            version: 1,
-            name: "create_t".into(),
-            up_sql: "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);".into(),
+            version: 2,
+            name: "broken".into(),
+            up_sql: "NOT VALID SQL;".into(),
             down_sql: None,
         });
+        assert!(engine.apply_pending().is_err());
 
-        let result = engine.apply_pending();
-        assert!(result.is_err());
-        let err = result.unwrap_err().to_string();
-        assert!(err.contains("checksum mismatch"));
+        let history = engine.history().expect("history");
+        assert_eq!(history.len(), 2);
+        assert!(history[0].success);
+        assert!(!history[1].success);
     }
 
     #[test]
-    fn file_based_loading() {
+    fn history_keeps_applies_from_a_restored_run() {
         let tmp = TempDir::new().expect("tmp dir");
+        let pool = ConnectionPool::new(tmp.path().join("test.db")).expect("pool");
+        let mut engine = MigrationEngine::new(pool);
+        engine.set_backup_dir(tmp.path().join("backups"));
+        engine.register(table_migration(1, "a"));
+        engine.apply_pending().expect("apply");
 
-        std::fs::write(
-            tmp.path().join("001_create_users.sql"),
-            "CREATE TABLE users (id INTEGER PRIMARY KEY);",
-        )
-        .expect("write file");
+        // One run: 2 succeeds, 3 fails, and the backup from before 2 is restored
+        engine
+            .register(table_migration(2, "b"))
+            .register(Migration {
+                version: 3,
+                name: "broken".into(),
+                up_sql: "NOT VALID SQL;".into(),
+                down_sql: None,
+            });
+        assert!(engine.apply_pending().is_err());
 
-        std::fs::write(
-            tmp.path().join("002_create_posts.sql"),
-            "CREATE TABLE posts (id INTEGER PRIMARY KEY);",
-        )
-        .expect("write file");
+        let history = engine.history().expect("history");
+        let events: Vec<_> = history.iter().map(|e| (e.version, e.success)).collect();
+        assert_eq!(events, [(1, true), (2, true), (3, false)]);
+        assert!(!engine.status().expect("status")[1].applied);
+    }
 
+    fn legacy_users_db() -> ConnectionPool {
         let pool = test_pool();
+        let conn = pool.get().expect("conn");
+        conn.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);")
+            .expect("hand-made table");
+        drop(conn);
+        pool
+    }
+
+    #[test]
+    fn baseline_marks_without_running() {
+        let pool = legacy_users_db();
         let mut engine = MigrationEngine::new(pool);
-        engine.register_from_dir(tmp.path()).expect("load dir");
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_users".into(),
+                up_sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);".into(),
+                down_sql: Some("DROP TABLE users;".into()),
+            })
+            .register(table_migration(2, "posts"));
+
+        let statuses = engine.baseline(1).expect("baseline");
+        assert!(statuses[0].applied);
+        assert!(!statuses[1].applied);
+
+        // Without the baseline this would fail with "table users already exists"
+        let statuses = engine.apply_pending().expect("apply");
+        assert!(statuses.iter().all(|s| s.applied));
 
-        assert_eq!(engine.migrations.len(), 2);
-        assert_eq!(engine.migrations[0].version, 1);
-        assert_eq!(engine.migrations[0].name, "create_users");
-        assert_eq!(engine.migrations[1].version, 2);
+        let history = engine.history().expect("history");
+        assert_eq!(history[0].kind, MigrationEventKind::Baseline);
+        assert_eq!(history[1].kind, MigrationEventKind::Apply);
     }
 
     #[test]
-    fn file_based_with_down_section() {
-        let tmp = TempDir::new().expect("tmp dir");
+    fn baseline_verified_checks_tables() {
+        let pool = legacy_users_db();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(Migration {
+                version: 1,
+                name: "create_users".into(),
+                up_sql: "CREATE TABLE IF NOT EXISTS \"users\" (id INTEGER PRIMARY KEY);".into(),
+                down_sql: None,
+            })
+            .register(table_migration(2, "posts"));
 
-        std::fs::write(
-            tmp.path().join("001_create_users.sql"),
-            "CREATE TABLE users (id INTEGER PRIMARY KEY);\n-- DOWN\nDROP TABLE users;",
-        )
-        .expect("write file");
+        engine.baseline_verified(1).expect("users exists");
+
+        let err = engine.baseline_verified(2).expect_err("posts is missing");
+        assert!(matches!(
+            err,
+            ShipKitError::BaselineMismatch { version: 2, ref missing_tables } if missing_tables == &["posts"]
+        ));
+    }
+
+    #[test]
+    fn baseline_verified_follows_drops_and_renames() {
+        let pool = legacy_users_db();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(table_migration(1, "people"))
+            .register(Migration {
+                version: 2,
+                name: "rename_people".into(),
+                up_sql: "ALTER TABLE people RENAME TO users;
+                         CREATE TABLE tmp (id INTEGER);
+                         DROP TABLE tmp;"
+                    .into(),
+                down_sql: None,
+            });
+
+        let statuses = engine.baseline_verified(2).expect("baseline");
+        assert!(statuses.iter().all(|s| s.applied));
+    }
+
+    #[test]
+    fn baseline_requires_registered_migrations() {
+        let mut engine = MigrationEngine::new(test_pool());
+        engine.register(table_migration(5, "a"));
+        assert!(engine.baseline(1).is_err());
+    }
 
+    fn set(name: &str, depends_on: &[&str], migrations: Vec<Migration>) -> MigrationSet {
+        MigrationSet {
+            name: name.into(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            migrations,
+        }
+    }
+
+    #[test]
+    fn sets_have_independent_versions() {
         let pool = test_pool();
         let mut engine = MigrationEngine::new(pool);
-        engine.register_from_dir(tmp.path()).expect("load dir");
+        engine
+            .register(table_migration(1, "notes"))
+            .register_set(set("jobs", &[], vec![table_migration(1, "jobs")]));
+
+        let statuses = engine.apply_pending().expect("apply");
+        let applied: Vec<(&str, i64)> = statuses
+            .iter()
+            .filter(|s| s.applied)
+            .map(|s| (s.set.as_str(), s.version))
+            .collect();
+        assert_eq!(applied, [("jobs", 1), (DEFAULT_SET, 1)]);
+    }
+
+    #[test]
+    fn apply_set_applies_only_the_set_and_its_dependencies() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool);
+        engine
+            .register(table_migration(1, "notes"))
+            .register_set(set("tags", &[], vec![table_migration(1, "tags")]))
+            .register_set(set("jobs", &["tags"], vec![table_migration(1, "jobs")]))
+            .register_set(set("audit", &[], vec![table_migration(1, "audit")]));
 
-        assert_eq!(engine.migrations.len(), 1);
-        assert!(engine.migrations[0].down_sql.is_some());
+        let statuses = engine.apply_set("jobs").expect("apply set");
+        let applied: Vec<(&str, i64)> = statuses
+            .iter()
+            .filter(|s| s.applied)
+            .map(|s| (s.set.as_str(), s.version))
+            .collect();
+        assert_eq!(applied, [("tags", 1), ("jobs", 1)]);
+        assert!(engine.apply_set("missing").is_err());
+    }
+
+    #[test]
+    fn sets_apply_in_dependency_order() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine
+            .register(Migration {
+                version: 1,
+                name: "seed_tags".into(),
+                up_sql: "INSERT INTO tags (label) VALUES ('inbox');".into(),
+                down_sql: None,
+            })
+            .register_set(set(
+                "tags",
+                &["core"],
+                vec![Migration {
+                    version: 1,
+                    name: "create_tags".into(),
+                    up_sql: "CREATE TABLE tags (id INTEGER PRIMARY KEY, \
+                             label TEXT REFERENCES core_labels(label));"
+                        .into(),
+                    down_sql: None,
+                }],
+            ))
+            .register_set(set(
+                "core",
+                &[],
+                vec![Migration {
+                    version: 1,
+                    name: "create_core_labels".into(),
+                    up_sql: "CREATE TABLE core_labels (label TEXT PRIMARY KEY); \
+                             INSERT INTO core_labels VALUES ('inbox');"
+                        .into(),
+                    down_sql: None,
+                }],
+            ));
+
+        let order: Vec<&str> = engine
+            .sets()
+            .expect("sets")
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(order, ["core", "tags", DEFAULT_SET]);
+        engine.apply_pending().expect("apply");
+    }
+
+    #[test]
+    fn unknown_or_cyclic_dependencies_are_rejected() {
+        let mut engine = MigrationEngine::new(test_pool());
+        engine.register_set(set("a", &["missing"], vec![table_migration(1, "a")]));
+        assert!(engine.apply_pending().is_err());
+
+        let mut engine = MigrationEngine::new(test_pool());
+        engine
+            .register_set(set("a", &["b"], vec![table_migration(1, "a")]))
+            .register_set(set("b", &["a"], vec![table_migration(1, "b")]));
+        let err = engine.sets().err().expect("cycle");
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn other_engines_sets_are_not_orphans() {
+        let pool = test_pool();
+        let mut module = MigrationEngine::new(pool.clone());
+        module.register_set(set("jobs", &[], vec![table_migration(1, "jobs")]));
+        module.apply_pending().expect("module apply");
+
+        let mut app = MigrationEngine::new(pool);
+        app.register(table_migration(1, "notes"));
+        let statuses = app.apply_pending().expect("app apply");
+        assert_eq!(statuses.len(), 1);
+    }
+
+    #[test]
+    fn rollback_is_scoped_to_a_set() {
+        let pool = test_pool();
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine
+            .register(table_migration(1, "notes"))
+            .register_set(set("jobs", &[], vec![table_migration(1, "jobs")]));
+        engine.apply_pending().expect("apply");
+
+        let rolled_back = engine.rollback_last_in("jobs").expect("rollback");
+        assert_eq!(rolled_back.map(|s| s.set), Some("jobs".to_string()));
+
+        let conn = pool.get().expect("conn");
+        let tables = schema::table_names(&conn).expect("tables");
+        assert!(tables.contains("notes") && !tables.contains("jobs"));
+    }
+
+    #[test]
+    fn apply_to_stops_at_target_version() {
+        let mut engine = MigrationEngine::new(test_pool());
+        engine
+            .register(table_migration(1, "a"))
+            .register(table_migration(2, "b"))
+            .register(table_migration(3, "c"));
+
+        let statuses = engine.apply_to(2).expect("apply to 2");
+        let applied: Vec<i64> = statuses
+            .iter()
+            .filter(|s| s.applied)
+            .map(|s| s.version)
+            .collect();
+        assert_eq!(applied, [1, 2]);
+
+        let statuses = engine.apply_pending().expect("apply rest");
+        assert!(statuses.iter().all(|s| s.applied));
+    }
+
+    fn migrate_with_newer_build(pool: &ConnectionPool) {
+        let mut newer = MigrationEngine::new(pool.clone());
+        newer
+            .register(table_migration(1, "a"))
+            .register(table_migration(2, "b"));
+        newer.apply_pending().expect("newer build");
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let pool = test_pool();
+        migrate_with_newer_build(&pool);
+
+        let mut older = MigrationEngine::new(pool);
+        older.register(table_migration(1, "a"));
+        let err = older.apply_pending().expect_err("database is newer");
+        assert!(matches!(
+            err,
+            ShipKitError::SchemaTooNew {
+                database_version: 2,
+                supported_version: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            older.rollback_last().expect_err("database is newer"),
+            ShipKitError::SchemaTooNew { .. }
+        ));
+    }
+
+    #[test]
+    fn read_only_policy_leaves_newer_schema_alone() {
+        let pool = test_pool();
+        migrate_with_newer_build(&pool);
+
+        let mut older = MigrationEngine::new(pool.clone());
+        older
+            .register(table_migration(1, "a"))
+            .set_downgrade_policy(DowngradePolicy::ReadOnly);
+        let statuses = older.apply_pending().expect("read-only open");
+        assert!(statuses[0].applied);
         assert_eq!(
-            engine.migrations[0].down_sql.as_deref(),
-            Some("DROP TABLE users;")
+            older.compatibility().expect("compatibility"),
+            SchemaCompatibility::Newer {
+                set: DEFAULT_SET.into(),
+                database_version: 2,
+                supported_version: 1,
+            }
         );
+        assert!(older.rollback_last().is_err());
+
+        let conn = pool.get().expect("conn");
+        assert!(schema::table_names(&conn).expect("tables").contains("b"));
     }
 }
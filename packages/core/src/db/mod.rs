@@ -1,7 +1,24 @@
-//! Database connection pool and migration engine.
+//! Database connection pool, migration engine and seed data.
 
+pub mod docs;
+pub mod history;
+pub mod lint;
+mod lock;
 pub mod migration;
 pub mod pool;
+pub mod progress;
+pub mod schema;
+pub mod seed;
+pub mod verify;
 
-pub use migration::{Migration, MigrationEngine, MigrationStatus};
+pub use history::{MigrationEvent, MigrationEventKind};
+pub use lint::{LintRule, LintWarning};
+pub use migration::{
+    ChecksumMode, ChecksumRepair, DEFAULT_SET, DowngradePolicy, Migration, MigrationEngine,
+    MigrationPolicy, MigrationSet, MigrationStatus, SchemaCompatibility, TableRebuild,
+};
 pub use pool::ConnectionPool;
+pub use progress::MigrationProgress;
+pub use schema::DatabaseSchema;
+pub use seed::{SeedEnvironment, SeedStatus, Seeder};
+pub use verify::{ReversibilityOutcome, ReversibilityReport};
@@ -0,0 +1,162 @@
+//! Progress reporting for migration runs.
+
+/// A step in [`super::MigrationEngine::apply_pending`].
+///
+/// `index` is the 1-based position of the migration among those pending in
+/// this run, across all sets, and `total` is how many are pending, so a UI
+/// can show "2 of 5".
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MigrationProgress {
+    Started {
+        set: String,
+        version: i64,
+        name: String,
+        index: usize,
+        total: usize,
+    },
+    Finished {
+        set: String,
+        version: i64,
+        name: String,
+        index: usize,
+        total: usize,
+        duration_ms: u64,
+    },
+    Failed {
+        set: String,
+        version: i64,
+        name: String,
+        index: usize,
+        total: usize,
+        error: String,
+    },
+}
+
+/// Callback invoked for every [`MigrationProgress`] event.
+pub type ProgressObserver = Box<dyn Fn(&MigrationProgress) + Send + Sync>;
+
+/// Event name used by [`emit_to`].
+#[cfg(feature = "tauri")]
+pub const PROGRESS_EVENT: &str = "shipkit://migration-progress";
+
+/// Build an observer that emits each step to the frontend as a
+/// [`PROGRESS_EVENT`] event.
+///
+/// ```ignore
+/// engine.set_progress_observer(shipkit_core::db::progress::emit_to(app.handle().clone()));
+/// ```
+#[cfg(feature = "tauri")]
+pub fn emit_to<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+) -> impl Fn(&MigrationProgress) + Send + Sync + 'static {
+    move |progress| {
+        if let Err(e) = tauri::Emitter::emit(&app, PROGRESS_EVENT, progress) {
+            tracing::warn!(error = %e, "failed to emit migration progress");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::db::{ConnectionPool, Migration, MigrationEngine};
+
+    fn migration(version: i64, up_sql: &str) -> Migration {
+        Migration {
+            version,
+            name: format!("m{version}"),
+            up_sql: up_sql.into(),
+            down_sql: None,
+        }
+    }
+
+    fn observed(engine: &mut MigrationEngine) -> Arc<Mutex<Vec<MigrationProgress>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        engine.set_progress_observer(move |p| sink.lock().expect("lock").push(p.clone()));
+        events
+    }
+
+    #[test]
+    fn reports_each_pending_migration() {
+        let mut engine = MigrationEngine::new(ConnectionPool::in_memory().expect("pool"));
+        engine
+            .register(migration(1, "CREATE TABLE a (id INTEGER);"))
+            .register(migration(2, "CREATE TABLE b (id INTEGER);"));
+        let events = observed(&mut engine);
+
+        engine.apply_pending().expect("apply");
+
+        let events = events.lock().expect("lock");
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            MigrationProgress::Started {
+                set: "app".into(),
+                version: 1,
+                name: "m1".into(),
+                index: 1,
+                total: 2,
+            }
+        );
+        assert!(matches!(
+            events[3],
+            MigrationProgress::Finished {
+                version: 2,
+                index: 2,
+                total: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reports_failure_and_stops() {
+        let mut engine = MigrationEngine::new(ConnectionPool::in_memory().expect("pool"));
+        engine
+            .register(migration(1, "CREATE TABLE a (id INTEGER);"))
+            .register(migration(2, "NOT VALID SQL;"))
+            .register(migration(3, "CREATE TABLE c (id INTEGER);"));
+        let events = observed(&mut engine);
+
+        assert!(engine.apply_pending().is_err());
+
+        let events = events.lock().expect("lock");
+        assert_eq!(events.len(), 4);
+        assert!(matches!(
+            &events[3],
+            MigrationProgress::Failed {
+                version: 2,
+                index: 2,
+                total: 3,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn counts_only_pending_migrations() {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        let mut engine = MigrationEngine::new(pool);
+        engine.register(migration(1, "CREATE TABLE a (id INTEGER);"));
+        engine.apply_pending().expect("first apply");
+        engine.register(migration(2, "CREATE TABLE b (id INTEGER);"));
+        let events = observed(&mut engine);
+
+        engine.apply_pending().expect("second apply");
+
+        let events = events.lock().expect("lock");
+        assert!(matches!(
+            events[0],
+            MigrationProgress::Started {
+                version: 2,
+                index: 1,
+                total: 1,
+                ..
+            }
+        ));
+    }
+}
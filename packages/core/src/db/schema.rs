@@ -0,0 +1,282 @@
+//! Schema introspection helpers.
+
+use std::collections::BTreeSet;
+
+use crate::db::migration::normalize_sql;
+use crate::error::Result;
+
+/// Filter on `sqlite_master` that hides SQLite's own objects and ShipKit's
+/// `_shipkit_*` bookkeeping tables.
+const USER_OBJECTS: &str =
+    "name NOT LIKE 'sqlite_%' AND name NOT LIKE '\\_shipkit\\_%' ESCAPE '\\'";
+
+/// Structure of every user table, index, view and trigger in a database.
+///
+/// Built from `PRAGMA` introspection rather than the stored `CREATE`
+/// statements, so two schemas compare equal when they have the same shape
+/// even if they were written with different formatting.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DatabaseSchema {
+    pub tables: Vec<TableSchema>,
+    pub indexes: Vec<IndexSchema>,
+    /// Views and triggers, as `(name, normalized SQL)`.
+    pub views: Vec<(String, String)>,
+    pub triggers: Vec<(String, String)>,
+}
+
+/// A table's columns and outgoing foreign keys.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+    pub foreign_keys: Vec<ForeignKeySchema>,
+}
+
+/// One column as reported by `PRAGMA table_info`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    /// Declared type; empty when the column has none.
+    pub data_type: String,
+    pub not_null: bool,
+    pub default_value: Option<String>,
+    /// Position in the primary key (1-based), or 0 if not part of it.
+    pub primary_key: i64,
+}
+
+/// One column of a foreign key as reported by `PRAGMA foreign_key_list`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ForeignKeySchema {
+    pub column: String,
+    pub references_table: String,
+    /// Referenced column; `None` when the key targets the primary key implicitly.
+    pub references_column: Option<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+/// A named index (automatic indexes for `UNIQUE`/`PRIMARY KEY` are included).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct IndexSchema {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+impl DatabaseSchema {
+    /// Human-readable differences from `other`, empty if the schemas match.
+    pub fn diff(&self, other: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+        diff_named(
+            "table",
+            &self.tables,
+            &other.tables,
+            |t| &t.name,
+            &mut differences,
+        );
+        diff_named(
+            "index",
+            &self.indexes,
+            &other.indexes,
+            |i| &i.name,
+            &mut differences,
+        );
+        diff_named(
+            "view",
+            &self.views,
+            &other.views,
+            |v| &v.0,
+            &mut differences,
+        );
+        diff_named(
+            "trigger",
+            &self.triggers,
+            &other.triggers,
+            |t| &t.0,
+            &mut differences,
+        );
+        differences
+    }
+}
+
+fn diff_named<T: PartialEq>(
+    kind: &str,
+    before: &[T],
+    after: &[T],
+    name: impl Fn(&T) -> &String,
+    differences: &mut Vec<String>,
+) {
+    for item in before {
+        match after.iter().find(|other| name(other) == name(item)) {
+            None => differences.push(format!("{kind} {} was removed", name(item))),
+            Some(other) if other != item => {
+                differences.push(format!("{kind} {} was changed", name(item)));
+            }
+            Some(_) => {}
+        }
+    }
+    for item in after {
+        if !before.iter().any(|other| name(other) == name(item)) {
+            differences.push(format!("{kind} {} was added", name(item)));
+        }
+    }
+}
+
+/// Read the structure of the main database.
+pub fn inspect(conn: &rusqlite::Connection) -> Result<DatabaseSchema> {
+    let mut schema = DatabaseSchema::default();
+
+    for table in table_names(conn)? {
+        let mut stmt = conn.prepare(
+            "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid",
+        )?;
+        let columns = stmt
+            .query_map([&table], |row| {
+                Ok(ColumnSchema {
+                    name: row.get(0)?,
+                    data_type: row.get(1)?,
+                    not_null: row.get(2)?,
+                    default_value: row.get(3)?,
+                    primary_key: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT \"from\", \"table\", \"to\", on_update, on_delete
+             FROM pragma_foreign_key_list(?1) ORDER BY id, seq",
+        )?;
+        let foreign_keys = stmt
+            .query_map([&table], |row| {
+                Ok(ForeignKeySchema {
+                    column: row.get(0)?,
+                    references_table: row.get(1)?,
+                    references_column: row.get(2)?,
+                    on_update: row.get(3)?,
+                    on_delete: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt =
+            conn.prepare("SELECT name, \"unique\" FROM pragma_index_list(?1) ORDER BY name")?;
+        let indexes = stmt
+            .query_map([&table], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (name, unique) in indexes {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+            let columns = stmt
+                .query_map([&name], |row| row.get::<_, Option<String>>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+                .into_iter()
+                .map(|c| c.unwrap_or_else(|| "<expression>".to_string()))
+                .collect();
+            schema.indexes.push(IndexSchema {
+                name,
+                table: table.clone(),
+                columns,
+                unique,
+            });
+        }
+
+        schema.tables.push(TableSchema {
+            name: table,
+            columns,
+            foreign_keys,
+        });
+    }
+
+    schema.views = named_sql(conn, "view")?;
+    schema.triggers = named_sql(conn, "trigger")?;
+    Ok(schema)
+}
+
+fn named_sql(conn: &rusqlite::Connection, kind: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, sql FROM sqlite_master WHERE type = ?1 AND {USER_OBJECTS} ORDER BY name"
+    ))?;
+    let rows = stmt
+        .query_map([kind], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows
+        .into_iter()
+        .map(|(name, sql)| (name, normalize_sql(&sql)))
+        .collect())
+}
+
+/// Names of the user tables in the main database, excluding SQLite's own
+/// tables and ShipKit's `_shipkit_*` bookkeeping tables.
+pub fn table_names(conn: &rusqlite::Connection) -> Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND {USER_OBJECTS}"
+    ))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<BTreeSet<_>>>()?;
+    Ok(names)
+}
+
+/// Tables a SQL script leaves behind, found by replaying its
+/// `CREATE TABLE`, `DROP TABLE` and `ALTER TABLE ... RENAME TO` statements
+/// on top of `tables`.
+///
+/// This is a token scan, not a SQL parser: it is meant for migration scripts
+/// and ignores anything it does not recognise.
+pub(crate) fn apply_table_changes(sql: &str, tables: &mut BTreeSet<String>) {
+    for statement in sql.split(';') {
+        let tokens = tokenize(statement);
+        let words: Vec<String> = tokens.iter().map(|t| t.to_ascii_uppercase()).collect();
+        let is = |i: usize, word: &str| words.get(i).is_some_and(|w| w == word);
+
+        if is(0, "CREATE") {
+            // Temporary tables do not outlive the connection, so they are skipped
+            if !is(1, "TABLE") {
+                continue;
+            }
+            let mut i = 2;
+            if is(i, "IF") && is(i + 1, "NOT") && is(i + 2, "EXISTS") {
+                i += 3;
+            }
+            if let Some(name) = tokens.get(i) {
+                tables.insert(unquote(name));
+            }
+        } else if is(0, "DROP") && is(1, "TABLE") {
+            let i = if is(2, "IF") && is(3, "EXISTS") { 4 } else { 2 };
+            if let Some(name) = tokens.get(i) {
+                tables.remove(&unquote(name));
+            }
+        } else if is(0, "ALTER")
+            && is(1, "TABLE")
+            && is(3, "RENAME")
+            && is(4, "TO")
+            && let (Some(from), Some(to)) = (tokens.get(2), tokens.get(5))
+        {
+            tables.remove(&unquote(from));
+            tables.insert(unquote(to));
+        }
+    }
+}
+
+/// Split a statement into words, treating parentheses and commas as breaks
+/// and skipping `--` comments.
+pub(crate) fn tokenize(statement: &str) -> Vec<String> {
+    statement
+        .lines()
+        .map(|line| line.split("--").next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | ',')))
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Strip identifier quoting and any schema prefix: `main."Users"` becomes `Users`.
+pub(crate) fn unquote(name: &str) -> String {
+    let name = name.rsplit('.').next().unwrap_or(name);
+    name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'))
+        .to_string()
+}
@@ -0,0 +1,404 @@
+//! Seed data for development, tests and demos, kept out of the schema history.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::db::pool::ConnectionPool;
+use crate::error::{Result, ShipKitError};
+
+/// Environment a seed is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeedEnvironment {
+    Dev,
+    Test,
+    Demo,
+}
+
+impl SeedEnvironment {
+    /// Every environment, in declaration order.
+    pub const ALL: [Self; 3] = [Self::Dev, Self::Test, Self::Demo];
+
+    /// Name used in `_shipkit_seeds` and for seed directories.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Test => "test",
+            Self::Demo => "demo",
+        }
+    }
+}
+
+impl fmt::Display for SeedEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SeedEnvironment {
+    type Err = ShipKitError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|env| env.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ShipKitError::Other(format!("unknown seed environment: {s}")))
+    }
+}
+
+/// Rust seed function. Runs inside the seed's transaction.
+pub type SeedFn = Box<dyn Fn(&rusqlite::Connection) -> Result<()> + Send + Sync>;
+
+enum SeedAction {
+    Sql(String),
+    Function(SeedFn),
+}
+
+struct Seed {
+    name: String,
+    environments: Vec<SeedEnvironment>,
+    action: SeedAction,
+}
+
+/// Whether a seed for the current environment has run.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SeedStatus {
+    pub name: String,
+    pub ran: bool,
+    pub ran_at: Option<String>,
+}
+
+/// Runs seed data for one environment and tracks which seeds ran in
+/// `_shipkit_seeds`.
+///
+/// Seeds are not migrations: they have no version, no checksum and no down
+/// SQL, and are meant to be run after [`super::MigrationEngine::apply_pending`]
+/// has created the tables they fill. Each seed runs in its own transaction.
+pub struct Seeder {
+    pool: ConnectionPool,
+    environment: SeedEnvironment,
+    seeds: Vec<Seed>,
+}
+
+impl Seeder {
+    /// Create a seeder for `environment`. Does not create the tracking table yet.
+    pub fn new(pool: ConnectionPool, environment: SeedEnvironment) -> Self {
+        Self {
+            pool,
+            environment,
+            seeds: Vec::new(),
+        }
+    }
+
+    /// Environment whose seeds this seeder runs.
+    pub fn environment(&self) -> SeedEnvironment {
+        self.environment
+    }
+
+    /// Register a SQL seed for the given environments. Returns `&mut Self` for chaining.
+    pub fn register_sql(
+        &mut self,
+        name: impl Into<String>,
+        environments: &[SeedEnvironment],
+        sql: impl Into<String>,
+    ) -> &mut Self {
+        self.push(name.into(), environments, SeedAction::Sql(sql.into()))
+    }
+
+    /// Register a Rust seed function for the given environments.
+    /// Returns `&mut Self` for chaining.
+    pub fn register_fn(
+        &mut self,
+        name: impl Into<String>,
+        environments: &[SeedEnvironment],
+        seed: impl Fn(&rusqlite::Connection) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.push(
+            name.into(),
+            environments,
+            SeedAction::Function(Box::new(seed)),
+        )
+    }
+
+    fn push(
+        &mut self,
+        name: String,
+        environments: &[SeedEnvironment],
+        action: SeedAction,
+    ) -> &mut Self {
+        self.seeds.push(Seed {
+            name,
+            environments: environments.to_vec(),
+            action,
+        });
+        self
+    }
+
+    /// Load SQL seeds from per-environment subdirectories of `dir`.
+    ///
+    /// `dir/dev/*.sql` is registered for [`SeedEnvironment::Dev`], and so on.
+    /// The seed name is the file name without `.sql`; files run in name order.
+    /// Missing subdirectories are skipped.
+    pub fn register_from_dir(&mut self, dir: impl AsRef<Path>) -> Result<&mut Self> {
+        for environment in SeedEnvironment::ALL {
+            let env_dir = dir.as_ref().join(environment.as_str());
+            if !env_dir.is_dir() {
+                continue;
+            }
+            let mut entries: Vec<_> = std::fs::read_dir(&env_dir)?
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "sql"))
+                .collect();
+            entries.sort_by_key(|e| e.file_name());
+
+            for entry in entries {
+                let path = entry.path();
+                let name = path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let sql = std::fs::read_to_string(&path)?;
+                self.register_sql(name, &[environment], sql);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Run every seed for the current environment that has not run yet.
+    pub fn run(&self) -> Result<Vec<SeedStatus>> {
+        self.run_seeds(false)
+    }
+
+    /// Run every seed for the current environment again, including those
+    /// that already ran.
+    ///
+    /// The seeder cannot undo earlier runs, so seeds meant to be re-run
+    /// should clear their own rows first (or use `INSERT OR REPLACE`).
+    pub fn reseed(&self) -> Result<Vec<SeedStatus>> {
+        self.run_seeds(true)
+    }
+
+    fn run_seeds(&self, again: bool) -> Result<Vec<SeedStatus>> {
+        self.ensure_tracking_table()?;
+        let conn = self.pool.get()?;
+        for seed in self.active() {
+            let ran: bool = conn.query_row(
+                "SELECT EXISTS (
+                     SELECT 1 FROM _shipkit_seeds WHERE name = ?1 AND environment = ?2
+                 )",
+                rusqlite::params![seed.name, self.environment.as_str()],
+                |row| row.get(0),
+            )?;
+            if ran && !again {
+                continue;
+            }
+
+            let tx = conn.unchecked_transaction()?;
+            let result = match &seed.action {
+                SeedAction::Sql(sql) => tx.execute_batch(sql).map_err(ShipKitError::from),
+                SeedAction::Function(seed_fn) => seed_fn(&tx),
+            };
+            result.map_err(|e| ShipKitError::Other(format!("seed {} failed: {e}", seed.name)))?;
+            tx.execute(
+                "INSERT OR REPLACE INTO _shipkit_seeds (name, environment, ran_at)
+                 VALUES (?1, ?2, datetime('now'))",
+                rusqlite::params![seed.name, self.environment.as_str()],
+            )?;
+            tx.commit()?;
+            tracing::info!(seed = %seed.name, environment = %self.environment, "seed ran");
+        }
+        drop(conn);
+        self.status()
+    }
+
+    /// Status of every seed registered for the current environment.
+    pub fn status(&self) -> Result<Vec<SeedStatus>> {
+        self.ensure_tracking_table()?;
+        let conn = self.pool.get()?;
+        let mut stmt =
+            conn.prepare("SELECT ran_at FROM _shipkit_seeds WHERE name = ?1 AND environment = ?2")?;
+        let mut statuses = Vec::new();
+        for seed in self.active() {
+            let ran_at = match stmt.query_row(
+                rusqlite::params![seed.name, self.environment.as_str()],
+                |row| row.get::<_, String>(0),
+            ) {
+                Ok(ran_at) => Some(ran_at),
+                Err(rusqlite::Error::QueryReturnedNoRows) => None,
+                Err(e) => return Err(e.into()),
+            };
+            statuses.push(SeedStatus {
+                name: seed.name.clone(),
+                ran: ran_at.is_some(),
+                ran_at,
+            });
+        }
+        Ok(statuses)
+    }
+
+    fn active(&self) -> impl Iterator<Item = &Seed> {
+        self.seeds
+            .iter()
+            .filter(|s| s.environments.contains(&self.environment))
+    }
+
+    fn ensure_tracking_table(&self) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS _shipkit_seeds (
+                name TEXT NOT NULL,
+                environment TEXT NOT NULL,
+                ran_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (name, environment)
+            );",
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn notes_pool() -> ConnectionPool {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        pool.get()
+            .expect("conn")
+            .execute_batch("CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT NOT NULL);")
+            .expect("notes table");
+        pool
+    }
+
+    fn count_notes(pool: &ConnectionPool) -> i64 {
+        pool.get()
+            .expect("conn")
+            .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+            .expect("count")
+    }
+
+    #[test]
+    fn runs_each_seed_once() {
+        let pool = notes_pool();
+        let mut seeder = Seeder::new(pool.clone(), SeedEnvironment::Dev);
+        seeder.register_sql(
+            "notes",
+            &[SeedEnvironment::Dev],
+            "INSERT INTO notes (title) VALUES ('a'), ('b');",
+        );
+
+        let statuses = seeder.run().expect("run");
+        assert!(statuses[0].ran);
+        seeder.run().expect("second run");
+        assert_eq!(count_notes(&pool), 2);
+    }
+
+    #[test]
+    fn only_runs_seeds_for_its_environment() {
+        let pool = notes_pool();
+        let mut seeder = Seeder::new(pool.clone(), SeedEnvironment::Test);
+        seeder
+            .register_sql(
+                "demo_notes",
+                &[SeedEnvironment::Demo],
+                "INSERT INTO notes (title) VALUES ('demo');",
+            )
+            .register_fn("test_notes", &[SeedEnvironment::Test], |conn| {
+                conn.execute("INSERT INTO notes (title) VALUES ('test')", [])?;
+                Ok(())
+            });
+
+        let statuses = seeder.run().expect("run");
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].name, "test_notes");
+        assert_eq!(count_notes(&pool), 1);
+    }
+
+    #[test]
+    fn reseed_runs_again() {
+        let pool = notes_pool();
+        let mut seeder = Seeder::new(pool.clone(), SeedEnvironment::Demo);
+        seeder.register_sql(
+            "notes",
+            &[SeedEnvironment::Demo],
+            "DELETE FROM notes; INSERT INTO notes (title) VALUES ('a');",
+        );
+        seeder.run().expect("run");
+        pool.get()
+            .expect("conn")
+            .execute("INSERT INTO notes (title) VALUES ('user')", [])
+            .expect("user row");
+
+        seeder.reseed().expect("reseed");
+        assert_eq!(count_notes(&pool), 1);
+    }
+
+    #[test]
+    fn failed_seed_is_rolled_back_and_not_recorded() {
+        let pool = notes_pool();
+        let mut seeder = Seeder::new(pool.clone(), SeedEnvironment::Dev);
+        seeder.register_sql(
+            "broken",
+            &[SeedEnvironment::Dev],
+            "INSERT INTO notes (title) VALUES ('a'); INSERT INTO missing VALUES (1);",
+        );
+
+        assert!(seeder.run().is_err());
+        assert_eq!(count_notes(&pool), 0);
+        assert!(!seeder.status().expect("status")[0].ran);
+    }
+
+    #[test]
+    fn loads_seeds_from_environment_directories() {
+        let dir = TempDir::new().expect("tempdir");
+        std::fs::create_dir(dir.path().join("dev")).expect("dev dir");
+        std::fs::write(
+            dir.path().join("dev").join("010_notes.sql"),
+            "INSERT INTO notes (title) VALUES ('dev');",
+        )
+        .expect("write seed");
+
+        let pool = notes_pool();
+        let mut seeder = Seeder::new(pool.clone(), SeedEnvironment::Dev);
+        seeder.register_from_dir(dir.path()).expect("load");
+        let statuses = seeder.run().expect("run");
+        assert_eq!(statuses[0].name, "010_notes");
+        assert_eq!(count_notes(&pool), 1);
+    }
+
+    #[test]
+    fn same_file_name_is_tracked_per_environment() {
+        let dir = TempDir::new().expect("tempdir");
+        for environment in ["dev", "demo"] {
+            std::fs::create_dir(dir.path().join(environment)).expect("env dir");
+            std::fs::write(
+                dir.path().join(environment).join("010_notes.sql"),
+                format!("INSERT INTO notes (title) VALUES ('{environment}');"),
+            )
+            .expect("write seed");
+        }
+
+        let pool = notes_pool();
+        let mut dev = Seeder::new(pool.clone(), SeedEnvironment::Dev);
+        dev.register_from_dir(dir.path()).expect("load dev");
+        dev.run().expect("run dev");
+
+        let mut demo = Seeder::new(pool.clone(), SeedEnvironment::Demo);
+        demo.register_from_dir(dir.path()).expect("load demo");
+        assert!(!demo.status().expect("demo status")[0].ran);
+        let statuses = demo.run().expect("run demo");
+        assert!(statuses[0].ran);
+        assert_eq!(count_notes(&pool), 2);
+        assert!(dev.status().expect("dev status")[0].ran);
+    }
+
+    #[test]
+    fn parses_environment_names() {
+        assert_eq!(
+            "Demo".parse::<SeedEnvironment>().ok(),
+            Some(SeedEnvironment::Demo)
+        );
+        assert!("prod".parse::<SeedEnvironment>().is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Reversibility checks for registered migrations.
+
+use crate::db::migration::{Migration, MigrationEngine, run_script};
+use crate::db::schema::{self, DatabaseSchema};
+use crate::error::Result;
+
+/// Result of checking one migration with [`MigrationEngine::verify_reversible`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReversibilityReport {
+    pub set: String,
+    pub version: i64,
+    pub name: String,
+    pub outcome: ReversibilityOutcome,
+}
+
+/// What happened when a migration was applied, rolled back and re-applied.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ReversibilityOutcome {
+    /// Down SQL restored the previous schema and up SQL applied cleanly again.
+    Reversible,
+    /// The migration has no down SQL.
+    NoDownSql,
+    /// Down SQL ran but left a different schema behind.
+    SchemaMismatch { differences: Vec<String> },
+    /// Re-applying after the rollback produced a different schema than the
+    /// first apply.
+    ReapplyMismatch { differences: Vec<String> },
+    /// A step failed. Later migrations are not checked after an up failure.
+    Failed { step: String, error: String },
+}
+
+impl ReversibilityReport {
+    /// Whether the migration round-tripped cleanly.
+    pub fn is_reversible(&self) -> bool {
+        self.outcome == ReversibilityOutcome::Reversible
+    }
+}
+
+impl MigrationEngine {
+    /// Check that every registered migration can be rolled back.
+    ///
+    /// Runs against a private in-memory database, never the engine's own
+    /// pool: starting from an empty schema, each migration (set by set, in
+    /// dependency order) is applied, rolled back, compared with the schema
+    /// from before it ran, then applied again so the next migration starts
+    /// from the right place.
+    ///
+    /// ```ignore
+    /// assert!(engine.verify_reversible()?.iter().all(|r| r.is_reversible()));
+    /// ```
+    pub fn verify_reversible(&self) -> Result<Vec<ReversibilityReport>> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+
+        let migrations: Vec<(&str, &Migration)> = self
+            .sets()?
+            .into_iter()
+            .flat_map(|s| s.migrations.iter().map(move |m| (s.name.as_str(), m)))
+            .collect();
+
+        let mut reports = Vec::new();
+        for (set, migration) in migrations {
+            let before = schema::inspect(&conn)?;
+            if let Err(e) = run(&conn, migration, &migration.up_sql) {
+                reports.push(report(set, migration, failed("up", e)));
+                break;
+            }
+            let after = schema::inspect(&conn)?;
+
+            let Some(down_sql) = &migration.down_sql else {
+                reports.push(report(set, migration, ReversibilityOutcome::NoDownSql));
+                continue;
+            };
+            if let Err(e) = run(&conn, migration, down_sql) {
+                // The failed rollback left the up schema in place, so carry on from there
+                reports.push(report(set, migration, failed("down", e)));
+                continue;
+            }
+            let rolled_back = schema::inspect(&conn)?;
+
+            if let Err(e) = run(&conn, migration, &migration.up_sql) {
+                let outcome = match mismatch(&before, &rolled_back) {
+                    Some(differences) => ReversibilityOutcome::SchemaMismatch { differences },
+                    None => failed("re-apply", e),
+                };
+                reports.push(report(set, migration, outcome));
+                break;
+            }
+            let reapplied = schema::inspect(&conn)?;
+
+            let outcome = if let Some(differences) = mismatch(&before, &rolled_back) {
+                ReversibilityOutcome::SchemaMismatch { differences }
+            } else if let Some(differences) = mismatch(&after, &reapplied) {
+                ReversibilityOutcome::ReapplyMismatch { differences }
+            } else {
+                ReversibilityOutcome::Reversible
+            };
+            reports.push(report(set, migration, outcome));
+        }
+
+        Ok(reports)
+    }
+}
+
+/// Execute a migration script the way the engine does.
+pub(crate) fn run(
+    conn: &rusqlite::Connection,
+    migration: &Migration,
+    sql: &str,
+) -> rusqlite::Result<()> {
+    run_script(conn, migration, sql)?.commit()
+}
+
+fn mismatch(expected: &DatabaseSchema, actual: &DatabaseSchema) -> Option<Vec<String>> {
+    let differences = expected.diff(actual);
+    (!differences.is_empty()).then_some(differences)
+}
+
+fn failed(step: &str, error: rusqlite::Error) -> ReversibilityOutcome {
+    ReversibilityOutcome::Failed {
+        step: step.to_string(),
+        error: error.to_string(),
+    }
+}
+
+fn report(set: &str, migration: &Migration, outcome: ReversibilityOutcome) -> ReversibilityReport {
+    ReversibilityReport {
+        set: set.to_string(),
+        version: migration.version,
+        name: migration.name.clone(),
+        outcome,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ConnectionPool;
+
+    fn engine(migrations: Vec<Migration>) -> MigrationEngine {
+        let mut engine = MigrationEngine::new(ConnectionPool::in_memory().expect("pool"));
+        for migration in migrations {
+            engine.register(migration);
+        }
+        engine
+    }
+
+    fn migration(version: i64, up_sql: &str, down_sql: Option<&str>) -> Migration {
+        Migration {
+            version,
+            name: format!("m{version}"),
+            up_sql: up_sql.into(),
+            down_sql: down_sql.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn reversible_migrations_pass() {
+        let engine = engine(vec![
+            migration(
+                1,
+                "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+                Some("DROP TABLE users;"),
+            ),
+            migration(
+                2,
+                "ALTER TABLE users ADD COLUMN name TEXT; CREATE INDEX idx_name ON users(name);",
+                Some("DROP INDEX idx_name; ALTER TABLE users DROP COLUMN name;"),
+            ),
+        ]);
+
+        let reports = engine.verify_reversible().expect("verify");
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(ReversibilityReport::is_reversible));
+    }
+
+    #[test]
+    fn incomplete_down_is_a_mismatch() {
+        let engine = engine(vec![migration(
+            1,
+            "CREATE TABLE a (id INTEGER PRIMARY KEY); CREATE TABLE b (id INTEGER PRIMARY KEY);",
+            Some("DROP TABLE a;"),
+        )]);
+
+        let reports = engine.verify_reversible().expect("verify");
+        assert!(matches!(
+            &reports[0].outcome,
+            ReversibilityOutcome::SchemaMismatch { differences }
+                if differences == &["table b was added"]
+        ));
+    }
+
+    #[test]
+    fn missing_down_sql_is_reported() {
+        let engine = engine(vec![
+            migration(1, "CREATE TABLE a (id INTEGER PRIMARY KEY);", None),
+            migration(
+                2,
+                "CREATE TABLE b (id INTEGER PRIMARY KEY);",
+                Some("DROP TABLE b;"),
+            ),
+        ]);
+
+        let reports = engine.verify_reversible().expect("verify");
+        assert_eq!(reports[0].outcome, ReversibilityOutcome::NoDownSql);
+        assert!(reports[1].is_reversible());
+    }
+
+    #[test]
+    fn failing_down_sql_is_reported() {
+        let engine = engine(vec![migration(
+            1,
+            "CREATE TABLE a (id INTEGER PRIMARY KEY);",
+            Some("DROP TABLE missing;"),
+        )]);
+
+        let reports = engine.verify_reversible().expect("verify");
+        assert!(matches!(
+            &reports[0].outcome,
+            ReversibilityOutcome::Failed { step, .. } if step == "down"
+        ));
+    }
+
+    #[test]
+    fn does_not_touch_engine_database() {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.register(migration(
+            1,
+            "CREATE TABLE a (id INTEGER PRIMARY KEY);",
+            Some("DROP TABLE a;"),
+        ));
+        engine.verify_reversible().expect("verify");
+
+        let conn = pool.get().expect("conn");
+        assert!(schema::table_names(&conn).expect("tables").is_empty());
+    }
+}
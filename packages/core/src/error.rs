@@ -20,6 +20,61 @@ pub enum ShipKitError {
     #[error("migration failed: {0}")]
     Migration(String),
 
+    #[error(
+        "out-of-order migrations in set '{set}': versions {versions:?} are pending but version {latest_applied} is already applied"
+    )]
+    MigrationOutOfOrder {
+        set: String,
+        versions: Vec<i64>,
+        latest_applied: i64,
+    },
+
+    #[error(
+        "migration gap in set '{set}': versions {versions:?} are applied but no longer registered"
+    )]
+    MigrationGap { set: String, versions: Vec<i64> },
+
+    #[error(
+        "orphaned migrations in set '{set}': versions {versions:?} are applied but not registered"
+    )]
+    MigrationOrphaned { set: String, versions: Vec<i64> },
+
+    #[error(
+        "database was migrated by a newer version: set '{set}' is at version {database_version} but this build only knows up to {supported_version}"
+    )]
+    SchemaTooNew {
+        set: String,
+        database_version: i64,
+        supported_version: i64,
+    },
+
+    #[error(
+        "cannot baseline at version {version}: expected tables are missing: {missing_tables:?}"
+    )]
+    BaselineMismatch {
+        version: i64,
+        missing_tables: Vec<String>,
+    },
+
+    #[error("migrations are locked by {owner} (since {acquired_at})")]
+    MigrationLocked { owner: String, acquired_at: String },
+
+    #[error("{source} (database restored from backup {})", backup_path.display())]
+    MigrationRestored {
+        source: Box<ShipKitError>,
+        backup_path: std::path::PathBuf,
+    },
+
+    #[error(
+        "{source}; restoring backup {} also failed: {restore_error}",
+        backup_path.display()
+    )]
+    MigrationRestoreFailed {
+        source: Box<ShipKitError>,
+        backup_path: std::path::PathBuf,
+        restore_error: Box<ShipKitError>,
+    },
+
     #[error("setting not found: {namespace}.{key}")]
     SettingNotFound { namespace: String, key: String },
 
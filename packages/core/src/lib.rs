@@ -16,10 +16,15 @@ pub mod settings;
 pub mod theme;
 
 // Re-exports for convenience
-pub use db::{ConnectionPool, Migration, MigrationEngine, MigrationStatus};
+pub use db::{
+    ConnectionPool, Migration, MigrationEngine, MigrationEvent, MigrationSet, MigrationStatus,
+    SeedEnvironment, Seeder,
+};
 pub use error::{Result, ShipKitError};
 pub use logger::{Logger, LoggerConfig};
-pub use settings::{Settings, SettingsBackend, SettingsManager, SqliteSettingsStore};
+pub use settings::{
+    SettingChange, Settings, SettingsBackend, SettingsManager, SqliteSettingsStore,
+};
 pub use theme::{ThemeDefinition, ThemeEngine, ThemeMode};
 
 // Re-export the derive macro so users write `use shipkit_core::Settings;`
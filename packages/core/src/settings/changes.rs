@@ -0,0 +1,165 @@
+//! Change notifications for [`SettingsManager`](super::SettingsManager).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde_json::Value;
+
+/// One setting that changed through a [`SettingsManager`](super::SettingsManager).
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SettingChange {
+    pub namespace: String,
+    pub key: String,
+    /// Stored value before the write; `None` if the key was unset.
+    pub old_value: Option<Value>,
+    /// Stored value after the write; `None` after a delete.
+    pub new_value: Option<Value>,
+}
+
+/// Event name used by [`emit_to`].
+#[cfg(feature = "tauri")]
+pub const CHANGED_EVENT: &str = "settings://changed";
+
+/// Payload of a [`CHANGED_EVENT`] event; `value` is `null` after a delete.
+#[cfg(feature = "tauri")]
+#[derive(Clone, serde::Serialize)]
+struct ChangedPayload<'a> {
+    namespace: &'a str,
+    key: &'a str,
+    value: Option<&'a Value>,
+}
+
+/// Build a callback for [`SettingsManager::on_change`] that emits each change
+/// to every window as a [`CHANGED_EVENT`] event.
+///
+/// ```ignore
+/// settings.on_change(shipkit_core::settings::changes::emit_to(app.handle().clone()));
+/// ```
+///
+/// [`SettingsManager::on_change`]: super::SettingsManager::on_change
+#[cfg(feature = "tauri")]
+pub fn emit_to<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+) -> impl Fn(&SettingChange) + Send + Sync + 'static {
+    move |change| {
+        let payload = ChangedPayload {
+            namespace: &change.namespace,
+            key: &change.key,
+            value: change.new_value.as_ref(),
+        };
+        if let Err(e) = tauri::Emitter::emit(&app, CHANGED_EVENT, payload) {
+            tracing::warn!(error = %e, "failed to emit settings change");
+        }
+    }
+}
+
+/// Handle for a callback registered with
+/// [`SettingsManager::on_change`](super::SettingsManager::on_change), used to
+/// remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type ChangeCallback = Arc<dyn Fn(&SettingChange) + Send + Sync>;
+
+enum Sink {
+    Channel(Sender<SettingChange>),
+    Callback(ChangeCallback),
+}
+
+struct Subscriber {
+    id: u64,
+    /// `None` matches every namespace.
+    namespace: Option<String>,
+    /// `None` matches every key in the namespace.
+    key: Option<String>,
+    sink: Sink,
+}
+
+impl Subscriber {
+    fn matches(&self, change: &SettingChange) -> bool {
+        self.namespace
+            .as_ref()
+            .is_none_or(|ns| *ns == change.namespace)
+            && self.key.as_ref().is_none_or(|key| *key == change.key)
+    }
+}
+
+/// Registered subscribers of one manager.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    list: Mutex<Vec<Subscriber>>,
+    next_id: AtomicU64,
+}
+
+impl Subscribers {
+    pub(crate) fn channel(&self, namespace: &str, key: Option<&str>) -> Receiver<SettingChange> {
+        let (sender, receiver) = mpsc::channel();
+        self.push(
+            Some(namespace.to_string()),
+            key.map(str::to_string),
+            Sink::Channel(sender),
+        );
+        receiver
+    }
+
+    pub(crate) fn callback(
+        &self,
+        callback: impl Fn(&SettingChange) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        SubscriptionId(self.push(None, None, Sink::Callback(Arc::new(callback))))
+    }
+
+    pub(crate) fn remove(&self, id: SubscriptionId) -> bool {
+        let mut list = self.list.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = list.len();
+        list.retain(|s| s.id != id.0);
+        list.len() != before
+    }
+
+    /// Deliver `change` to every matching subscriber.
+    ///
+    /// Channels whose receiver was dropped are removed. Callbacks run after
+    /// the list is unlocked, so they may subscribe or write settings.
+    pub(crate) fn notify(&self, change: SettingChange) {
+        let callbacks: Vec<ChangeCallback> = {
+            let mut list = self.list.lock().unwrap_or_else(PoisonError::into_inner);
+            list.retain(|s| match &s.sink {
+                Sink::Channel(sender) if s.matches(&change) => sender.send(change.clone()).is_ok(),
+                _ => true,
+            });
+            list.iter()
+                .filter(|s| s.matches(&change))
+                .filter_map(|s| match &s.sink {
+                    Sink::Callback(callback) => Some(Arc::clone(callback)),
+                    Sink::Channel(_) => None,
+                })
+                .collect()
+        };
+        for callback in callbacks {
+            callback(&change);
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.list
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    fn push(&self, namespace: Option<String>, key: Option<String>, sink: Sink) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.list
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Subscriber {
+                id,
+                namespace,
+                key,
+                sink,
+            });
+        id
+    }
+}
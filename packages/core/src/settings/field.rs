@@ -0,0 +1,52 @@
+//! Typed keys for individual settings fields.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Key of one field of settings type `S`, whose value has type `T`.
+///
+/// `#[derive(Settings)]` generates one as an associated constant per field
+/// (`AppearanceSettings::THEME`), for use with
+/// [`SettingsManager::get_typed`](super::SettingsManager::get_typed) and
+/// [`SettingsManager::set_typed`](super::SettingsManager::set_typed).
+pub struct Field<S, T> {
+    key: &'static str,
+    _types: PhantomData<fn() -> (S, T)>,
+}
+
+impl<S, T> Field<S, T> {
+    /// A key for the field stored under `key`. The derive only creates keys
+    /// for fields that exist.
+    pub const fn new(key: &'static str) -> Self {
+        Self {
+            key,
+            _types: PhantomData,
+        }
+    }
+
+    /// Storage key of the field.
+    pub const fn key(&self) -> &'static str {
+        self.key
+    }
+}
+
+// Manual impls: deriving would require `S` and `T` to implement these too.
+impl<S, T> Clone for Field<S, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S, T> Copy for Field<S, T> {}
+
+impl<S, T> fmt::Debug for Field<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Field").field(&self.key).finish()
+    }
+}
+
+impl<S, T> fmt::Display for Field<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key)
+    }
+}
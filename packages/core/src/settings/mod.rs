@@ -1,36 +1,112 @@
 //! Type-safe settings management with SQLite persistence.
 
+pub mod changes;
+pub mod field;
 pub mod store;
 pub mod traits;
+pub mod typescript;
+pub mod upgrade;
+pub mod validate;
 
+use std::collections::{BTreeMap, HashSet};
+use std::sync::mpsc::Receiver;
+use std::sync::{Mutex, PoisonError};
+
+pub use changes::{SettingChange, SubscriptionId};
+pub use field::Field;
 pub use store::SqliteSettingsStore;
-pub use traits::{Settings, SettingsBackend};
+pub use traits::{SettingWrite, Settings, SettingsBackend};
+
+use changes::Subscribers;
 
 /// Convenience wrapper that combines a store with type-safe access.
+///
+/// Writes made through the manager are reported to its subscribers; writes
+/// that go to the store directly are not.
+///
+/// The first time the manager reads or writes a settings type's namespace, it
+/// runs [`Settings::upgrade`], so values stored under an older version are
+/// migrated whichever method touches them first.
 pub struct SettingsManager {
     store: Box<dyn SettingsBackend>,
+    subscribers: Subscribers,
+    /// Each registered settings type, by namespace.
+    registered: BTreeMap<String, Registration>,
+    /// Namespaces already brought up to date by [`Settings::upgrade`].
+    upgraded: Mutex<HashSet<String>>,
+}
+
+/// What the manager keeps of a type passed to [`SettingsManager::register`].
+struct Registration {
+    schema: serde_json::Value,
+    fields: fn() -> &'static [&'static str],
+    validate_field: fn(&str, &serde_json::Value) -> crate::error::Result<()>,
+    upgrade: UpgradeStore,
 }
 
+type UpgradeStore = fn(&dyn SettingsBackend) -> crate::error::Result<()>;
+
 impl SettingsManager {
     /// Create a new manager wrapping the given backend.
     pub fn new(store: impl SettingsBackend + 'static) -> Self {
         Self {
             store: Box::new(store),
+            subscribers: Subscribers::default(),
+            registered: BTreeMap::new(),
+            upgraded: Mutex::new(HashSet::new()),
         }
     }
 
+    /// Register settings type `S` so its [`Settings::schema`] can be looked
+    /// up by namespace, e.g. by a frontend that renders a form per namespace,
+    /// and raw writes to its namespace are validated.
+    pub fn register<S: Settings>(&mut self) -> &mut Self {
+        self.registered.insert(
+            S::namespace().to_string(),
+            Registration {
+                schema: S::schema(),
+                fields: S::fields,
+                validate_field: S::validate_field,
+                upgrade: S::upgrade,
+            },
+        );
+        self
+    }
+
+    /// JSON Schema of the settings type registered for `namespace`.
+    pub fn schema(&self, namespace: &str) -> Option<&serde_json::Value> {
+        self.registered.get(namespace).map(|r| &r.schema)
+    }
+
+    /// Namespaces with a registered settings type, in sorted order.
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.registered.keys().map(String::as_str)
+    }
+
     /// Load settings of type `S`, filling missing fields with defaults.
     pub fn load<S: Settings>(&self) -> crate::error::Result<S> {
+        self.upgrade::<S>()?;
         S::load(self.store.as_ref())
     }
 
     /// Save all fields of a settings struct.
+    ///
+    /// Subscribers hear about each field whose stored value changed.
     pub fn save<S: Settings>(&self, settings: &S) -> crate::error::Result<()> {
-        settings.save(self.store.as_ref())
+        self.upgrade::<S>()?;
+        let mut old = self.store.get_all(S::namespace())?;
+        settings.save(self.store.as_ref())?;
+        if let serde_json::Value::Object(map) = serde_json::to_value(settings)? {
+            for (key, value) in map {
+                self.changed(S::namespace(), &key, old.remove(&key), Some(value));
+            }
+        }
+        Ok(())
     }
 
     /// Get a single field's value.
     pub fn get<S: Settings>(&self, field: &str) -> crate::error::Result<serde_json::Value> {
+        self.upgrade::<S>()?;
         S::get_field(self.store.as_ref(), field)
     }
 
@@ -40,6 +116,433 @@ impl SettingsManager {
         field: &str,
         value: serde_json::Value,
     ) -> crate::error::Result<()> {
-        S::set_field(self.store.as_ref(), field, value)
+        self.upgrade::<S>()?;
+        let old = self.store.get(S::namespace(), field)?;
+        S::set_field(self.store.as_ref(), field, value.clone())?;
+        self.changed(S::namespace(), field, old, Some(value));
+        Ok(())
+    }
+
+    /// Get a single field as its Rust type.
+    pub fn get_typed<S: Settings, T: serde::de::DeserializeOwned>(
+        &self,
+        field: Field<S, T>,
+    ) -> crate::error::Result<T> {
+        let value = self.get::<S>(field.key())?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Set a single field from its Rust type, validating and notifying like
+    /// [`Self::set`].
+    pub fn set_typed<S: Settings, T: serde::Serialize>(
+        &self,
+        field: Field<S, T>,
+        value: T,
+    ) -> crate::error::Result<()> {
+        self.set::<S>(field.key(), serde_json::to_value(value)?)
+    }
+
+    /// Delete a single field's stored value, so it reads as its default again.
+    pub fn delete<S: Settings>(&self, field: &str) -> crate::error::Result<()> {
+        if !S::fields().contains(&field) {
+            return Err(crate::error::ShipKitError::SettingNotFound {
+                namespace: S::namespace().to_string(),
+                key: field.to_string(),
+            });
+        }
+        self.upgrade::<S>()?;
+        let old = self.store.get(S::namespace(), field)?;
+        self.store.delete(S::namespace(), field)?;
+        self.changed(S::namespace(), field, old, None);
+        Ok(())
+    }
+
+    /// Get a raw value by namespace and key, without defaults.
+    pub fn get_value(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> crate::error::Result<Option<serde_json::Value>> {
+        self.upgrade_registered(namespace)?;
+        self.store.get(namespace, key)
+    }
+
+    /// Get every stored value in a namespace.
+    pub fn get_all(
+        &self,
+        namespace: &str,
+    ) -> crate::error::Result<std::collections::HashMap<String, serde_json::Value>> {
+        self.upgrade_registered(namespace)?;
+        self.store.get_all(namespace)
+    }
+
+    /// Check a raw value the way [`Self::set_value`] does before writing it.
+    ///
+    /// In a namespace registered with [`Self::register`], `key` must be one
+    /// of the type's fields and `value` must pass its
+    /// [`Settings::validate_field`]. Other namespaces accept anything.
+    pub fn validate_value(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> crate::error::Result<()> {
+        let Some(registration) = self.registered.get(namespace) else {
+            return Ok(());
+        };
+        if !(registration.fields)().contains(&key) {
+            return Err(crate::error::ShipKitError::SettingNotFound {
+                namespace: namespace.to_string(),
+                key: key.to_string(),
+            });
+        }
+        (registration.validate_field)(key, value)
+    }
+
+    /// Set a raw value by namespace and key, for settings without a
+    /// [`Settings`] struct, or written by name (e.g. from a frontend).
+    ///
+    /// Values in registered namespaces are checked with
+    /// [`Self::validate_value`] first.
+    pub fn set_value(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: serde_json::Value,
+    ) -> crate::error::Result<()> {
+        self.validate_value(namespace, key, &value)?;
+        self.upgrade_registered(namespace)?;
+        let old = self.store.get(namespace, key)?;
+        self.store.set(namespace, key, value.clone())?;
+        self.changed(namespace, key, old, Some(value));
+        Ok(())
+    }
+
+    /// Delete a raw value by namespace and key.
+    pub fn delete_value(&self, namespace: &str, key: &str) -> crate::error::Result<()> {
+        self.upgrade_registered(namespace)?;
+        let old = self.store.get(namespace, key)?;
+        self.store.delete(namespace, key)?;
+        self.changed(namespace, key, old, None);
+        Ok(())
+    }
+
+    /// Receive a [`SettingChange`] for every field of `S` that changes.
+    ///
+    /// Dropping the receiver ends the subscription.
+    pub fn subscribe<S: Settings>(&self) -> Receiver<SettingChange> {
+        self.subscribers.channel(S::namespace(), None)
+    }
+
+    /// Receive a [`SettingChange`] whenever `namespace.key` changes.
+    pub fn subscribe_key(&self, namespace: &str, key: &str) -> Receiver<SettingChange> {
+        self.subscribers.channel(namespace, Some(key))
+    }
+
+    /// Call `callback` for every change in any namespace.
+    ///
+    /// The callback runs on the thread that made the write, after the write
+    /// succeeded.
+    pub fn on_change(
+        &self,
+        callback: impl Fn(&SettingChange) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.subscribers.callback(callback)
+    }
+
+    /// Remove a callback registered with [`Self::on_change`]. Returns whether
+    /// it was still registered.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.subscribers.remove(id)
+    }
+
+    /// Run `S`'s upgrade the first time its namespace is used.
+    fn upgrade<S: Settings>(&self) -> crate::error::Result<()> {
+        self.upgrade_once(S::namespace(), S::upgrade)
+    }
+
+    /// Like [`Self::upgrade`] for raw access by namespace: only namespaces
+    /// with a registered type have anything to run.
+    fn upgrade_registered(&self, namespace: &str) -> crate::error::Result<()> {
+        match self.registered.get(namespace) {
+            Some(registration) => self.upgrade_once(namespace, registration.upgrade),
+            None => Ok(()),
+        }
+    }
+
+    fn upgrade_once(&self, namespace: &str, upgrade: UpgradeStore) -> crate::error::Result<()> {
+        let mut upgraded = self.upgraded.lock().unwrap_or_else(PoisonError::into_inner);
+        if !upgraded.contains(namespace) {
+            upgrade(self.store.as_ref())?;
+            upgraded.insert(namespace.to_string());
+        }
+        Ok(())
+    }
+
+    /// Notify subscribers unless the stored value stayed the same.
+    fn changed(
+        &self,
+        namespace: &str,
+        key: &str,
+        old_value: Option<serde_json::Value>,
+        new_value: Option<serde_json::Value>,
+    ) {
+        if old_value == new_value {
+            return;
+        }
+        self.subscribers.notify(SettingChange {
+            namespace: namespace.to_string(),
+            key: key.to_string(),
+            old_value,
+            new_value,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::ConnectionPool;
+    use crate::error::{Result, ShipKitError};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Appearance {
+        theme: String,
+        font_size: i64,
+    }
+
+    impl Settings for Appearance {
+        fn namespace() -> &'static str {
+            "appearance"
+        }
+
+        fn fields() -> &'static [&'static str] {
+            &["theme", "font_size"]
+        }
+
+        fn field_default(field: &str) -> Result<serde_json::Value> {
+            match field {
+                "theme" => Ok(json!("dark")),
+                "font_size" => Ok(json!(14)),
+                _ => Err(ShipKitError::SettingNotFound {
+                    namespace: Self::namespace().to_string(),
+                    key: field.to_string(),
+                }),
+            }
+        }
+
+        fn load(store: &dyn SettingsBackend) -> Result<Self> {
+            let mut map = serde_json::Map::new();
+            for field in Self::fields() {
+                map.insert(field.to_string(), Self::get_field(store, field)?);
+            }
+            Ok(serde_json::from_value(serde_json::Value::Object(map))?)
+        }
+
+        fn save(&self, store: &dyn SettingsBackend) -> Result<()> {
+            store.set(Self::namespace(), "theme", json!(self.theme))?;
+            store.set(Self::namespace(), "font_size", json!(self.font_size))
+        }
+
+        fn get_field(store: &dyn SettingsBackend, field: &str) -> Result<serde_json::Value> {
+            if let Some(value) = store.get(Self::namespace(), field)? {
+                return Ok(value);
+            }
+            Self::field_default(field)
+        }
+
+        fn set_field(
+            store: &dyn SettingsBackend,
+            field: &str,
+            value: serde_json::Value,
+        ) -> Result<()> {
+            if !Self::fields().contains(&field) {
+                return Err(ShipKitError::SettingNotFound {
+                    namespace: Self::namespace().to_string(),
+                    key: field.to_string(),
+                });
+            }
+            store.set(Self::namespace(), field, value)
+        }
+    }
+
+    fn manager() -> SettingsManager {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        SettingsManager::new(SqliteSettingsStore::new(pool).expect("store"))
+    }
+
+    #[test]
+    fn set_and_delete_notify_with_old_and_new_values() {
+        let manager = manager();
+        let changes = manager.subscribe::<Appearance>();
+
+        manager
+            .set::<Appearance>("theme", json!("light"))
+            .expect("set");
+        manager
+            .set::<Appearance>("theme", json!("solarized"))
+            .expect("set");
+        manager.delete::<Appearance>("theme").expect("delete");
+
+        let received: Vec<_> = changes.try_iter().collect();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[0].old_value, None);
+        assert_eq!(received[0].new_value, Some(json!("light")));
+        assert_eq!(received[1].old_value, Some(json!("light")));
+        assert_eq!(received[1].new_value, Some(json!("solarized")));
+        assert_eq!(received[2].old_value, Some(json!("solarized")));
+        assert_eq!(received[2].new_value, None);
+    }
+
+    #[test]
+    fn save_reports_only_changed_fields() {
+        let manager = manager();
+        manager
+            .save(&Appearance {
+                theme: "dark".into(),
+                font_size: 14,
+            })
+            .expect("save");
+        let font_size = manager.subscribe_key("appearance", "font_size");
+        let theme = manager.subscribe_key("appearance", "theme");
+
+        manager
+            .save(&Appearance {
+                theme: "dark".into(),
+                font_size: 16,
+            })
+            .expect("save");
+
+        let change = font_size.try_recv().expect("font_size change");
+        assert_eq!(change.old_value, Some(json!(14)));
+        assert_eq!(change.new_value, Some(json!(16)));
+        assert!(theme.try_recv().is_err());
+    }
+
+    #[test]
+    fn failed_writes_do_not_notify() {
+        let manager = manager();
+        let changes = manager.subscribe::<Appearance>();
+
+        assert!(manager.set::<Appearance>("unknown", json!(1)).is_err());
+        assert!(manager.delete::<Appearance>("unknown").is_err());
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[test]
+    fn callbacks_fire_until_unsubscribed() {
+        let manager = manager();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let id = manager.on_change(move |c| sink.lock().expect("lock").push(c.key.clone()));
+
+        manager
+            .set::<Appearance>("font_size", json!(12))
+            .expect("set");
+        assert!(manager.unsubscribe(id));
+        manager
+            .set::<Appearance>("font_size", json!(18))
+            .expect("set");
+
+        assert_eq!(*seen.lock().expect("lock"), ["font_size"]);
+        assert!(!manager.unsubscribe(id));
+    }
+
+    #[test]
+    fn raw_writes_notify_key_subscribers() {
+        let manager = manager();
+        let changes = manager.subscribe_key("shipkit_internal", "active_theme");
+
+        manager
+            .set_value("shipkit_internal", "active_theme", json!("light"))
+            .expect("set");
+        manager
+            .set_value("shipkit_internal", "other", json!(1))
+            .expect("set");
+        manager
+            .delete_value("shipkit_internal", "active_theme")
+            .expect("delete");
+
+        let received: Vec<_> = changes.try_iter().collect();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].new_value, Some(json!("light")));
+        assert_eq!(received[1].new_value, None);
+        assert_eq!(
+            manager
+                .get_value("shipkit_internal", "active_theme")
+                .expect("get"),
+            None
+        );
+    }
+
+    #[test]
+    fn registered_schemas_are_looked_up_by_namespace() {
+        let mut manager = manager();
+        assert!(manager.schema("appearance").is_none());
+
+        manager.register::<Appearance>();
+        let schema = manager.schema("appearance").expect("registered");
+        assert_eq!(schema["properties"]["font_size"]["default"], json!(14));
+        assert_eq!(manager.namespaces().collect::<Vec<_>>(), ["appearance"]);
+    }
+
+    #[test]
+    fn raw_writes_to_registered_namespaces_are_checked() {
+        let mut manager = manager();
+        manager.register::<Appearance>();
+        let changes = manager.subscribe::<Appearance>();
+
+        let err = manager
+            .set_value("appearance", "unknown", json!(1))
+            .expect_err("unknown field");
+        assert!(matches!(err, ShipKitError::SettingNotFound { .. }));
+        assert!(changes.try_recv().is_err());
+        assert_eq!(manager.get_value("appearance", "unknown").expect("get"), None);
+
+        manager
+            .set_value("appearance", "theme", json!("light"))
+            .expect("known field");
+        manager
+            .set_value("unregistered", "anything", json!(1))
+            .expect("unregistered namespace");
+    }
+
+    #[test]
+    fn dropped_receivers_are_pruned() {
+        let manager = manager();
+        drop(manager.subscribe::<Appearance>());
+        manager
+            .set::<Appearance>("theme", json!("light"))
+            .expect("set");
+        assert_eq!(manager.subscribers.len(), 0);
+    }
+
+    #[test]
+    fn default_typescript_lists_fields_as_unknown() {
+        assert_eq!(
+            Appearance::typescript(),
+            "export interface Appearance {\n  theme: unknown;\n  font_size: unknown;\n}\n\n\
+             export const APPEARANCE_NAMESPACE = \"appearance\";\n\n\
+             export const APPEARANCE_DEFAULTS: Appearance = {\n  theme: \"dark\",\n  font_size: 14,\n};\n"
+        );
+    }
+
+    #[test]
+    fn typed_fields_read_and_write_rust_values() {
+        const THEME: Field<Appearance, String> = Field::new("theme");
+        const FONT_SIZE: Field<Appearance, i64> = Field::new("font_size");
+
+        let manager = manager();
+        let changes = manager.subscribe::<Appearance>();
+        assert_eq!(manager.get_typed(THEME).expect("default"), "dark");
+
+        manager.set_typed(FONT_SIZE, 16).expect("set");
+        assert_eq!(manager.get_typed(FONT_SIZE).expect("get"), 16);
+        assert_eq!(changes.try_recv().expect("change").key, "font_size");
+        assert_eq!(FONT_SIZE.to_string(), "font_size");
     }
 }
@@ -2,31 +2,64 @@
 
 use std::collections::HashMap;
 
-use crate::db::ConnectionPool;
+use crate::db::{ConnectionPool, Migration, MigrationEngine, MigrationSet};
 use crate::error::Result;
-use crate::settings::traits::SettingsBackend;
+use crate::settings::traits::{SettingWrite, SettingsBackend};
+
+/// Name of the migration set that owns the `_shipkit_settings` table.
+pub const MIGRATION_SET: &str = "settings";
 
 /// SQLite implementation of [`SettingsBackend`].
 ///
-/// Creates its own `_shipkit_settings` table on construction.
+/// Its `_shipkit_settings` table is managed by the [`MIGRATION_SET`]
+/// migration set, which is applied on construction.
 pub struct SqliteSettingsStore {
     pool: ConnectionPool,
 }
 
 impl SqliteSettingsStore {
-    /// Create a new store, creating the settings table if needed.
+    /// Create a new store, applying the settings migrations if needed with
+    /// a default [`MigrationEngine`].
+    ///
+    /// Applications with their own engine should use [`Self::with_engine`],
+    /// so its lock, backup and policy settings apply.
     pub fn new(pool: ConnectionPool) -> Result<Self> {
-        let conn = pool.get()?;
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS _shipkit_settings (
+        Self::with_engine(pool.clone(), &mut MigrationEngine::new(pool))
+    }
+
+    /// Create a new store whose migrations are applied by the application's
+    /// `engine`.
+    ///
+    /// [`Self::migrations`] is registered with `engine` unless it already
+    /// is, and only that set is applied now; the rest of the application's
+    /// migrations are left pending.
+    pub fn with_engine(pool: ConnectionPool, engine: &mut MigrationEngine) -> Result<Self> {
+        let registered = engine.sets()?.iter().any(|s| s.name == MIGRATION_SET);
+        if !registered {
+            engine.register_set(Self::migrations());
+        }
+        engine.apply_set(MIGRATION_SET)?;
+        Ok(Self { pool })
+    }
+
+    /// Migrations for the settings table.
+    pub fn migrations() -> MigrationSet {
+        let mut set = MigrationSet::new(MIGRATION_SET);
+        set.migrations.push(Migration {
+            version: 1,
+            name: "create_settings".into(),
+            // IF NOT EXISTS: databases from before this set created the table ad hoc
+            up_sql: "CREATE TABLE IF NOT EXISTS _shipkit_settings (
                 namespace TEXT NOT NULL,
                 key TEXT NOT NULL,
                 value TEXT NOT NULL,
                 updated_at TEXT NOT NULL DEFAULT (datetime('now')),
                 PRIMARY KEY (namespace, key)
-            );",
-        )?;
-        Ok(Self { pool })
+            );"
+            .into(),
+            down_sql: Some("DROP TABLE IF EXISTS _shipkit_settings;".into()),
+        });
+        set
     }
 }
 
@@ -82,12 +115,32 @@ impl SettingsBackend for SqliteSettingsStore {
         )?;
         Ok(())
     }
+
+    fn write_batch(&self, writes: &[SettingWrite<'_>]) -> Result<()> {
+        let conn = self.pool.get()?;
+        let tx = conn.unchecked_transaction()?;
+        for write in writes {
+            match &write.value {
+                Some(value) => tx.execute(
+                    "INSERT OR REPLACE INTO _shipkit_settings (namespace, key, value, updated_at)
+                     VALUES (?1, ?2, ?3, datetime('now'))",
+                    rusqlite::params![write.namespace, write.key, serde_json::to_string(value)?],
+                )?,
+                None => tx.execute(
+                    "DELETE FROM _shipkit_settings WHERE namespace = ?1 AND key = ?2",
+                    rusqlite::params![write.namespace, write.key],
+                )?,
+            };
+        }
+        tx.commit()?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::ConnectionPool;
+    use crate::db::{ConnectionPool, DEFAULT_SET};
 
     fn test_store() -> SqliteSettingsStore {
         let pool = ConnectionPool::in_memory().expect("pool");
@@ -143,6 +196,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn table_is_tracked_in_its_own_set() {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        SqliteSettingsStore::new(pool.clone()).expect("store");
+        // Constructing again must not re-run or reject the applied migration
+        SqliteSettingsStore::new(pool.clone()).expect("second store");
+
+        let conn = pool.get().expect("conn");
+        let rows: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM _shipkit_migrations WHERE \"set\" = ?1",
+                [MIGRATION_SET],
+                |row| row.get(0),
+            )
+            .expect("count");
+        assert_eq!(rows, 1);
+    }
+
+    #[test]
+    fn with_engine_leaves_application_migrations_pending() {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        let mut engine = MigrationEngine::new(pool.clone());
+        engine.register(Migration {
+            version: 1,
+            name: "create_notes".into(),
+            up_sql: "CREATE TABLE notes (id INTEGER PRIMARY KEY);".into(),
+            down_sql: None,
+        });
+
+        let store = SqliteSettingsStore::with_engine(pool, &mut engine).expect("store");
+        store.set("ns", "key", serde_json::json!(1)).expect("set");
+
+        let statuses = engine.status().expect("status");
+        let applied: Vec<(&str, bool)> = statuses
+            .iter()
+            .map(|s| (s.set.as_str(), s.applied))
+            .collect();
+        assert_eq!(applied, [(MIGRATION_SET, true), (DEFAULT_SET, false)]);
+    }
+
     #[test]
     fn delete_value() {
         let store = test_store();
@@ -21,6 +21,29 @@ pub trait SettingsBackend: Send + Sync {
 
     /// Delete a single setting.
     fn delete(&self, namespace: &str, key: &str) -> Result<()>;
+
+    /// Apply `writes` in order, all or nothing where the backend can.
+    ///
+    /// The default applies them one at a time with [`Self::set`] and
+    /// [`Self::delete`]; [`super::SqliteSettingsStore`] uses one transaction.
+    fn write_batch(&self, writes: &[SettingWrite<'_>]) -> Result<()> {
+        for write in writes {
+            match &write.value {
+                Some(value) => self.set(write.namespace, write.key, value.clone())?,
+                None => self.delete(write.namespace, write.key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One change in a [`SettingsBackend::write_batch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingWrite<'a> {
+    pub namespace: &'a str,
+    pub key: &'a str,
+    /// New value, or `None` to delete the key.
+    pub value: Option<serde_json::Value>,
 }
 
 /// Trait for type-safe settings structs.
@@ -30,8 +53,31 @@ pub trait Settings: Sized + serde::Serialize + serde::de::DeserializeOwned {
     /// Namespace prefix for storage (e.g., "appearance").
     fn namespace() -> &'static str;
 
-    /// Returns `(field_name, default_value_as_json)` for each field.
-    fn field_defaults() -> &'static [(&'static str, &'static str)];
+    /// Names of the stored fields, in declaration order.
+    fn fields() -> &'static [&'static str];
+
+    /// Default value of one field, computed when it is needed.
+    ///
+    /// Returns [`crate::error::ShipKitError::SettingNotFound`] for unknown
+    /// fields.
+    fn field_default(field: &str) -> Result<serde_json::Value>;
+
+    /// Schema version of the struct, recorded per namespace by
+    /// [`Self::upgrade`]. Set with `#[settings(version = N)]`.
+    fn version() -> u32 {
+        0
+    }
+
+    /// Migrate values stored by older versions of the struct (renamed
+    /// fields, changed formats) before they are read.
+    ///
+    /// The default does nothing. `#[derive(Settings)]` runs
+    /// [`crate::settings::upgrade::run`] from the generated `load`, `save` and
+    /// `set_field` when the struct declares a `version` or fields declare
+    /// `rename_from`.
+    fn upgrade(_store: &dyn SettingsBackend) -> Result<()> {
+        Ok(())
+    }
 
     /// Load from store, filling any missing fields with defaults.
     fn load(store: &dyn SettingsBackend) -> Result<Self>;
@@ -42,6 +88,65 @@ pub trait Settings: Sized + serde::Serialize + serde::de::DeserializeOwned {
     /// Get one field's value.
     fn get_field(store: &dyn SettingsBackend, field: &str) -> Result<serde_json::Value>;
 
+    /// JSON Schema (draft 2020-12) describing the stored fields, for
+    /// rendering settings forms and validating input generically.
+    ///
+    /// The default only lists each field with its default value.
+    /// `#[derive(Settings)]` adds types, nullability, validator constraints
+    /// and doc comments.
+    fn schema() -> serde_json::Value {
+        let properties: serde_json::Map<String, serde_json::Value> = Self::fields()
+            .iter()
+            .map(|name| {
+                let default = Self::field_default(name).ok();
+                (name.to_string(), serde_json::json!({ "default": default }))
+            })
+            .collect();
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "properties": properties,
+        })
+    }
+
+    /// TypeScript declarations for the struct: an interface named after the
+    /// type, and `NAMESPACE`/`DEFAULTS` constants prefixed with its name.
+    ///
+    /// The default types every field as `unknown`. `#[derive(Settings)]`
+    /// maps field types, `Option` and `one_of` (as literal unions), and
+    /// copies doc comments. Collect these into a file with
+    /// [`crate::settings::typescript::bindings`].
+    fn typescript() -> String {
+        let path = std::any::type_name::<Self>();
+        let path = path.split('<').next().unwrap_or(path);
+        let name = path.rsplit("::").next().unwrap_or(path);
+        let constant = crate::settings::typescript::constant_prefix(name);
+        let fields: Vec<_> = Self::fields()
+            .iter()
+            .map(|field| {
+                let name = crate::settings::typescript::property_name(field);
+                format!("  {name}: unknown;\n")
+            })
+            .collect();
+        format!(
+            "export interface {name} {{\n{}}}\n\n\
+             export const {constant}_NAMESPACE = {};\n\n\
+             export const {constant}_DEFAULTS: {name} = {{\n{}}};\n",
+            fields.concat(),
+            serde_json::Value::from(Self::namespace()),
+            crate::settings::typescript::defaults::<Self>(),
+        )
+    }
+
+    /// Check a value for one field before it is stored.
+    ///
+    /// The default accepts anything. `#[derive(Settings)]` generates checks
+    /// from the `min`, `max`, `one_of`, `pattern` and `non_empty` field
+    /// attributes (see [`crate::settings::validate`]).
+    fn validate_field(_field: &str, _value: &serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
     /// Set one field's value.
     fn set_field(
         store: &dyn SettingsBackend,
@@ -0,0 +1,134 @@
+//! TypeScript bindings generated from settings types.
+//!
+//! Each [`Settings::typescript`](super::Settings::typescript) definition is
+//! collected into one module that the frontend imports, so its code is
+//! type-checked against the Rust structs. Keep the checked-in file current
+//! from a test:
+//!
+//! ```ignore
+//! #[test]
+//! fn settings_bindings_are_current() {
+//!     let contents = bindings(&[AppearanceSettings::typescript()]);
+//!     let update = std::env::var_os("SHIPKIT_UPDATE_BINDINGS").is_some();
+//!     write_or_check("../src/lib/settings.generated.ts", &contents, update).unwrap();
+//! }
+//! ```
+
+use std::path::Path;
+
+use super::Settings;
+use crate::error::{Result, ShipKitError};
+
+/// First line of every generated module.
+pub const HEADER: &str = "// Generated from the Rust settings types. Do not edit by hand.\n";
+
+/// A TypeScript module containing `definitions`, in order.
+pub fn bindings(definitions: &[String]) -> String {
+    let mut out = HEADER.to_string();
+    for definition in definitions {
+        out.push('\n');
+        out.push_str(definition);
+    }
+    out
+}
+
+/// Body of a `DEFAULTS` object literal: one `  field: value,` line per
+/// field, with the defaults computed now. A default that fails to serialize
+/// is written as `null`, which the interface will usually reject.
+pub fn defaults<S: Settings>() -> String {
+    S::fields()
+        .iter()
+        .map(|field| {
+            let value = S::field_default(field).unwrap_or_default();
+            format!("  {}: {value},\n", property_name(field))
+        })
+        .collect()
+}
+
+/// `field` as an object key: bare when it is an identifier, quoted otherwise
+/// (`kebab-case` renames).
+pub fn property_name(field: &str) -> String {
+    let identifier = !field.starts_with(|c: char| c.is_ascii_digit())
+        && !field.is_empty()
+        && field
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if identifier {
+        field.to_string()
+    } else {
+        serde_json::Value::from(field).to_string()
+    }
+}
+
+/// Prefix of the constants generated for a type: `AppearanceSettings` ->
+/// `APPEARANCE_SETTINGS`, `HTTPConfig` -> `HTTP_CONFIG`.
+pub fn constant_prefix(type_name: &str) -> String {
+    let chars: Vec<char> = type_name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_uppercase());
+    }
+    out
+}
+
+/// Write `contents` to `path` when `update` is set; otherwise fail unless
+/// the file already holds exactly `contents`.
+pub fn write_or_check(path: impl AsRef<Path>, contents: &str, update: bool) -> Result<()> {
+    let path = path.as_ref();
+    let current = match std::fs::read_to_string(path) {
+        Ok(current) => Some(current),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if current.as_deref() == Some(contents) {
+        return Ok(());
+    }
+    if update {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+        return Ok(());
+    }
+    Err(ShipKitError::Other(format!(
+        "{} is out of date with the Rust settings types; rerun with SHIPKIT_UPDATE_BINDINGS=1",
+        path.display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_prefix_splits_words() {
+        assert_eq!(constant_prefix("AppearanceSettings"), "APPEARANCE_SETTINGS");
+        assert_eq!(constant_prefix("HTTPConfig"), "HTTP_CONFIG");
+        assert_eq!(constant_prefix("Editor2Settings"), "EDITOR2_SETTINGS");
+        assert_eq!(constant_prefix("demo"), "DEMO");
+    }
+
+    #[test]
+    fn write_or_check_only_writes_on_update() {
+        let dir = tempfile::TempDir::new().expect("tempdir");
+        let path = dir.path().join("lib/settings.generated.ts");
+        let contents = bindings(&["export interface A {}\n".to_string()]);
+        assert!(contents.starts_with(HEADER));
+
+        assert!(write_or_check(&path, &contents, false).is_err());
+        write_or_check(&path, &contents, true).expect("write");
+        assert_eq!(std::fs::read_to_string(&path).expect("read"), contents);
+        write_or_check(&path, &contents, false).expect("up to date");
+        assert!(write_or_check(&path, "stale", false).is_err());
+    }
+}
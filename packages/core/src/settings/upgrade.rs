@@ -0,0 +1,265 @@
+//! Forward migration of stored settings when a settings struct changes shape.
+//!
+//! `#[derive(Settings)]` implements
+//! [`Settings::upgrade`](super::Settings::upgrade), which the generated
+//! `load`, `save` and `set_field` call first, with [`run`] for structs that
+//! declare `#[settings(version = N)]` or have fields with
+//! `#[settings(rename_from = "...")]`:
+//!
+//! 1. Values stored under a field's old names move to its current name. If
+//!    both exist, the current one wins and the old key is dropped.
+//! 2. If the namespace was last written by an older version, the struct's
+//!    `upgrade` function runs once per version step, oldest first.
+//! 3. Changed keys are written back, removed keys deleted, and the new
+//!    version recorded under [`VERSIONS_NAMESPACE`], all in one
+//!    [`SettingsBackend::write_batch`].
+//!
+//! Namespaces with no stored values are simply stamped with the current
+//! version. A namespace recorded at a *newer* version than the struct (after
+//! a downgrade) is left untouched.
+
+use serde_json::{Map, Value};
+
+use super::{SettingWrite, SettingsBackend};
+use crate::error::{Result, ShipKitError};
+
+/// Namespace holding each settings namespace's schema version, keyed by
+/// namespace.
+pub const VERSIONS_NAMESPACE: &str = "_shipkit.versions";
+
+/// Upgrades stored values from version `from` to `from + 1` in place.
+pub type UpgradeFn = fn(from: u32, values: &mut Map<String, Value>) -> Result<()>;
+
+/// Schema version recorded for `namespace`, if any.
+pub fn stored_version(store: &dyn SettingsBackend, namespace: &str) -> Result<Option<u32>> {
+    let Some(value) = store.get(VERSIONS_NAMESPACE, namespace)? else {
+        return Ok(None);
+    };
+    value
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .map(Some)
+        .ok_or_else(|| ShipKitError::InvalidSetting {
+            key: namespace.to_string(),
+            reason: format!("stored schema version {value} is not a version number"),
+        })
+}
+
+/// Bring `namespace` up to `version`.
+///
+/// `renames` lists `(field, old_names)` pairs. Namespaces stored before
+/// versioning was added count as version 0.
+pub fn run(
+    store: &dyn SettingsBackend,
+    namespace: &str,
+    version: u32,
+    renames: &[(&str, &[&str])],
+    upgrade: Option<UpgradeFn>,
+) -> Result<()> {
+    let stored = stored_version(store, namespace)?;
+    if let Some(stored) = stored
+        && stored > version
+    {
+        tracing::warn!(
+            namespace,
+            stored,
+            version,
+            "settings were written by a newer version; skipping upgrade"
+        );
+        return Ok(());
+    }
+
+    let before: Map<String, Value> = store.get_all(namespace)?.into_iter().collect();
+    let mut values = before.clone();
+
+    for (field, old_names) in renames {
+        for old in *old_names {
+            let Some(value) = values.remove(*old) else {
+                continue;
+            };
+            if values.contains_key(*field) {
+                continue;
+            }
+            tracing::info!(namespace, from = old, to = field, "renamed stored setting");
+            values.insert(field.to_string(), value);
+        }
+    }
+
+    let from = if before.is_empty() {
+        version
+    } else {
+        stored.unwrap_or(0)
+    };
+    if from < version {
+        tracing::info!(namespace, from, to = version, "upgrading stored settings");
+        if let Some(upgrade) = upgrade {
+            for step in from..version {
+                upgrade(step, &mut values)?;
+            }
+        }
+    }
+
+    let mut writes = Vec::new();
+    for (key, value) in &values {
+        if before.get(key) != Some(value) {
+            writes.push(SettingWrite {
+                namespace,
+                key,
+                value: Some(value.clone()),
+            });
+        }
+    }
+    for key in before.keys() {
+        if !values.contains_key(key) {
+            writes.push(SettingWrite {
+                namespace,
+                key,
+                value: None,
+            });
+        }
+    }
+    if stored != Some(version) {
+        writes.push(SettingWrite {
+            namespace: VERSIONS_NAMESPACE,
+            key: namespace,
+            value: Some(Value::from(version)),
+        });
+    }
+    if writes.is_empty() {
+        return Ok(());
+    }
+    store.write_batch(&writes)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::ConnectionPool;
+    use crate::settings::SqliteSettingsStore;
+
+    fn store() -> SqliteSettingsStore {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        SqliteSettingsStore::new(pool).expect("store")
+    }
+
+    fn values(store: &SqliteSettingsStore) -> Map<String, Value> {
+        store
+            .get_all("editor")
+            .expect("get_all")
+            .into_iter()
+            .collect()
+    }
+
+    /// v0 -> v1 doubles `size`; v1 -> v2 renames `mode` values.
+    fn upgrade(from: u32, values: &mut Map<String, Value>) -> Result<()> {
+        match from {
+            0 => {
+                if let Some(size) = values.get("size").and_then(Value::as_i64) {
+                    values.insert("size".into(), json!(size * 2));
+                }
+            }
+            1 => {
+                if values.get("mode") == Some(&json!("emacs")) {
+                    values.insert("mode".into(), json!("default"));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn renames_move_old_keys_unless_the_new_one_exists() {
+        let store = store();
+        store.set("editor", "tab_size", json!(2)).expect("set");
+        store.set("editor", "font", json!("Mono")).expect("set");
+        store
+            .set("editor", "font_family", json!("Serif"))
+            .expect("set");
+
+        run(
+            &store,
+            "editor",
+            0,
+            &[("tab_width", &["tab_size"]), ("font_family", &["font"])],
+            None,
+        )
+        .expect("run");
+
+        let values = values(&store);
+        assert_eq!(values.get("tab_width"), Some(&json!(2)));
+        assert_eq!(values.get("font_family"), Some(&json!("Serif")));
+        assert!(!values.contains_key("tab_size"));
+        assert!(!values.contains_key("font"));
+        assert_eq!(stored_version(&store, "editor").expect("version"), Some(0));
+    }
+
+    #[test]
+    fn upgrades_run_once_per_version_step() {
+        let store = store();
+        store.set("editor", "size", json!(7)).expect("set");
+        store.set("editor", "mode", json!("emacs")).expect("set");
+
+        run(&store, "editor", 2, &[], Some(upgrade)).expect("run");
+        assert_eq!(values(&store).get("size"), Some(&json!(14)));
+        assert_eq!(values(&store).get("mode"), Some(&json!("default")));
+        assert_eq!(stored_version(&store, "editor").expect("version"), Some(2));
+
+        // Already current: nothing runs again
+        run(&store, "editor", 2, &[], Some(upgrade)).expect("rerun");
+        assert_eq!(values(&store).get("size"), Some(&json!(14)));
+    }
+
+    #[test]
+    fn empty_and_newer_namespaces_are_not_upgraded() {
+        let store = store();
+        run(&store, "editor", 3, &[], Some(upgrade)).expect("fresh");
+        assert_eq!(stored_version(&store, "editor").expect("version"), Some(3));
+
+        store.set("editor", "size", json!(7)).expect("set");
+        run(&store, "editor", 1, &[], Some(upgrade)).expect("downgrade");
+        assert_eq!(values(&store).get("size"), Some(&json!(7)));
+        assert_eq!(stored_version(&store, "editor").expect("version"), Some(3));
+    }
+
+    #[test]
+    fn failed_upgrade_writes_nothing() {
+        fn failing(_: u32, values: &mut Map<String, Value>) -> Result<()> {
+            values.insert("size".into(), json!(0));
+            Err(ShipKitError::Other("bad data".into()))
+        }
+
+        let store = store();
+        store.set("editor", "size", json!(7)).expect("set");
+        assert!(run(&store, "editor", 1, &[], Some(failing)).is_err());
+        assert_eq!(values(&store).get("size"), Some(&json!(7)));
+        assert_eq!(stored_version(&store, "editor").expect("version"), None);
+    }
+
+    #[test]
+    fn failed_write_back_leaves_the_namespace_untouched() {
+        let pool = ConnectionPool::in_memory().expect("pool");
+        let store = SqliteSettingsStore::new(pool.clone()).expect("store");
+        store.set("editor", "tab_size", json!(2)).expect("set");
+        store.set("editor", "size", json!(7)).expect("set");
+        // The rename's delete of the old key fails after its insert ran
+        pool.get()
+            .expect("conn")
+            .execute_batch(
+                "CREATE TRIGGER keep_tab_size BEFORE DELETE ON _shipkit_settings
+                 WHEN old.key = 'tab_size'
+                 BEGIN SELECT RAISE(ABORT, 'tab_size is pinned'); END;",
+            )
+            .expect("trigger");
+
+        let renames: &[(&str, &[&str])] = &[("tab_width", &["tab_size"])];
+        assert!(run(&store, "editor", 1, renames, Some(upgrade)).is_err());
+        let values = values(&store);
+        assert_eq!(values.get("tab_size"), Some(&json!(2)));
+        assert_eq!(values.get("size"), Some(&json!(7)));
+        assert!(!values.contains_key("tab_width"));
+        assert_eq!(stored_version(&store, "editor").expect("version"), None);
+    }
+}
@@ -0,0 +1,229 @@
+//! Value checks behind the `#[derive(Settings)]` validation attributes.
+//!
+//! The derive turns each field's type, and its `#[settings(min = ..,
+//! max = .., one_of = [..], pattern = "..", non_empty)]` attributes, into
+//! calls to these functions from the generated `validate_field`. Each returns
+//! [`ShipKitError::InvalidSetting`] naming the rule that failed and the
+//! offending value.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{Result, ShipKitError};
+
+/// Deserializes into the field's type `T`; `type_name` is how the field
+/// declares it, for the message.
+pub fn of_type<T: DeserializeOwned>(key: &str, value: &Value, type_name: &str) -> Result<()> {
+    serde_json::from_value::<T>(value.clone())
+        .map(|_| ())
+        .map_err(|e| invalid(key, format!("{value} is not a valid {type_name}: {e}")))
+}
+
+/// The stored value if it still deserializes into `T`, otherwise the field's
+/// default from `default`.
+///
+/// A stored value that no longer fits (after a type change, or a write that
+/// bypassed `set_field`) is logged and ignored rather than failing the whole
+/// `load`. It stays in the store until the field is next written. A default
+/// that doesn't fit `T` either is an error.
+pub fn stored_or_default<T: DeserializeOwned>(
+    namespace: &str,
+    key: &str,
+    stored: Option<Value>,
+    default: impl FnOnce() -> Result<Value>,
+) -> Result<Value> {
+    if let Some(value) = stored {
+        match serde_json::from_value::<T>(value.clone()) {
+            Ok(_) => return Ok(value),
+            Err(e) => tracing::warn!(
+                namespace,
+                key,
+                value = %value,
+                error = %e,
+                "stored setting does not match its type; using the default"
+            ),
+        }
+    }
+    let default = default()?;
+    serde_json::from_value::<T>(default.clone())
+        .map_err(|e| invalid(key, format!("invalid default {default}: {e}")))?;
+    Ok(default)
+}
+
+/// Number no smaller than `min`.
+pub fn min(key: &str, value: &Value, min: f64) -> Result<()> {
+    let number = number(key, value)?;
+    if number < min {
+        return Err(invalid(key, format!("must be at least {min}, got {value}")));
+    }
+    Ok(())
+}
+
+/// Number no larger than `max`.
+pub fn max(key: &str, value: &Value, max: f64) -> Result<()> {
+    let number = number(key, value)?;
+    if number > max {
+        return Err(invalid(key, format!("must be at most {max}, got {value}")));
+    }
+    Ok(())
+}
+
+/// Equal to one of `allowed`.
+pub fn one_of(key: &str, value: &Value, allowed: &[Value]) -> Result<()> {
+    if allowed.contains(value) {
+        return Ok(());
+    }
+    let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+    Err(invalid(
+        key,
+        format!("must be one of [{}], got {value}", allowed.join(", ")),
+    ))
+}
+
+/// Regular expression of a `pattern` rule, compiled on first use.
+///
+/// The derive declares one as a `static` per field, so each pattern is
+/// compiled once rather than on every write.
+pub struct Pattern {
+    source: &'static str,
+    regex: OnceLock<std::result::Result<Regex, regex::Error>>,
+}
+
+impl Pattern {
+    /// `source` is only compiled when a value is first checked against it.
+    pub const fn new(source: &'static str) -> Self {
+        Self {
+            source,
+            regex: OnceLock::new(),
+        }
+    }
+}
+
+/// String matching the regular expression `pattern`.
+///
+/// The match is unanchored, as with [`regex::Regex::is_match`]; write
+/// `^...$` to require the whole string to match.
+pub fn pattern(key: &str, value: &Value, pattern: &Pattern) -> Result<()> {
+    let Value::String(s) = value else {
+        return Err(invalid(key, format!("expected a string, got {value}")));
+    };
+    let source = pattern.source;
+    let regex = pattern
+        .regex
+        .get_or_init(|| Regex::new(source))
+        .as_ref()
+        .map_err(|e| invalid(key, format!("invalid pattern {source}: {e}")))?;
+    if !regex.is_match(s) {
+        return Err(invalid(key, format!("must match {source}, got {value}")));
+    }
+    Ok(())
+}
+
+/// Not `null`, and not an empty string, array or object.
+pub fn non_empty(key: &str, value: &Value) -> Result<()> {
+    let empty = match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        Value::Bool(_) | Value::Number(_) => false,
+    };
+    if empty {
+        return Err(invalid(key, format!("must not be empty, got {value}")));
+    }
+    Ok(())
+}
+
+fn number(key: &str, value: &Value) -> Result<f64> {
+    value
+        .as_f64()
+        .ok_or_else(|| invalid(key, format!("expected a number, got {value}")))
+}
+
+fn invalid(key: &str, reason: String) -> ShipKitError {
+    ShipKitError::InvalidSetting {
+        key: key.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn reason(result: Result<()>) -> String {
+        match result {
+            Err(ShipKitError::InvalidSetting { reason, .. }) => reason,
+            other => format!("unexpected: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn type_checks_and_fallback() {
+        assert!(of_type::<f64>("font_scale", &json!(1.5), "f64").is_ok());
+        assert!(of_type::<Option<u32>>("max_tabs", &Value::Null, "Option<u32>").is_ok());
+        assert_eq!(
+            reason(of_type::<f64>("font_scale", &json!("big"), "f64")),
+            "\"big\" is not a valid f64: invalid type: string \"big\", expected f64"
+        );
+
+        let default = || Ok(json!(1.0));
+        let kept = stored_or_default::<f64>("appearance", "font_scale", Some(json!(2.0)), default);
+        assert_eq!(kept.expect("stored"), json!(2.0));
+        let fallback =
+            stored_or_default::<f64>("appearance", "font_scale", Some(json!("big")), default);
+        assert_eq!(fallback.expect("default"), json!(1.0));
+        let bad_default = || Ok(json!("big"));
+        assert!(stored_or_default::<f64>("appearance", "font_scale", None, bad_default).is_err());
+    }
+
+    #[test]
+    fn range_checks_report_the_bound() {
+        assert!(min("font_scale", &json!(0.5), 0.5).is_ok());
+        assert!(max("font_scale", &json!(3), 3.0).is_ok());
+        assert_eq!(
+            reason(min("font_scale", &json!(0.25), 0.5)),
+            "must be at least 0.5, got 0.25"
+        );
+        assert_eq!(
+            reason(max("font_scale", &json!(4), 3.0)),
+            "must be at most 3, got 4"
+        );
+        assert_eq!(
+            reason(min("font_scale", &json!("big"), 0.5)),
+            "expected a number, got \"big\""
+        );
+    }
+
+    #[test]
+    fn one_of_lists_the_allowed_values() {
+        let allowed = [json!("light"), json!("dark")];
+        assert!(one_of("theme", &json!("dark"), &allowed).is_ok());
+        assert_eq!(
+            reason(one_of("theme", &json!("blue"), &allowed)),
+            "must be one of [\"light\", \"dark\"], got \"blue\""
+        );
+    }
+
+    #[test]
+    fn pattern_and_non_empty() {
+        let hex = Pattern::new("^#[0-9a-f]{6}$");
+        assert!(pattern("accent", &json!("#3b82f6"), &hex).is_ok());
+        assert_eq!(
+            reason(pattern("accent", &json!("blue"), &hex)),
+            "must match ^#[0-9a-f]{6}$, got \"blue\""
+        );
+        let unclosed = Pattern::new("(");
+        assert!(reason(pattern("accent", &json!("x"), &unclosed)).starts_with("invalid pattern ("));
+        assert!(non_empty("name", &json!("x")).is_ok());
+        assert!(non_empty("count", &json!(0)).is_ok());
+        assert!(non_empty("name", &json!("")).is_err());
+        assert!(non_empty("tags", &json!([])).is_err());
+        assert!(non_empty("name", &Value::Null).is_err());
+    }
+}
@@ -1,32 +1,114 @@
 //! Proc macros for shipkit-core.
 
+mod schema;
+mod serde_attrs;
+mod typescript;
+mod validate;
+
 use darling::{FromDeriveInput, FromField};
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
 use syn::{parse_macro_input, DeriveInput};
 
 #[derive(FromField)]
-#[darling(attributes(settings))]
+#[darling(attributes(settings), forward_attrs(doc, serde))]
 struct SettingsFieldReceiver {
     ident: Option<syn::Ident>,
+    vis: syn::Visibility,
     ty: syn::Type,
+    attrs: Vec<syn::Attribute>,
     #[darling(default)]
     default: Option<syn::Lit>,
+    #[darling(default)]
+    default_fn: Option<syn::Path>,
+    #[darling(default)]
+    default_json: Option<syn::LitStr>,
+    #[darling(default)]
+    min: Option<syn::Expr>,
+    #[darling(default)]
+    max: Option<syn::Expr>,
+    #[darling(default)]
+    one_of: Option<syn::ExprArray>,
+    #[darling(default)]
+    pattern: Option<syn::LitStr>,
+    #[darling(default)]
+    non_empty: bool,
+    #[darling(multiple)]
+    rename_from: Vec<String>,
 }
 
 #[derive(FromDeriveInput)]
-#[darling(attributes(settings), supports(struct_named))]
+#[darling(
+    attributes(settings),
+    supports(struct_named),
+    forward_attrs(doc, serde)
+)]
 struct SettingsReceiver {
     ident: syn::Ident,
     generics: syn::Generics,
+    attrs: Vec<syn::Attribute>,
     data: darling::ast::Data<(), SettingsFieldReceiver>,
     namespace: String,
+    #[darling(default)]
+    version: Option<u32>,
+    #[darling(default)]
+    upgrade: Option<syn::Path>,
 }
 
 /// Derive the `Settings` trait for a struct.
 ///
 /// Requires `#[settings(namespace = "...")]` on the struct.
-/// Fields can use `#[settings(default = ...)]` to specify defaults.
+/// A field's default comes from at most one of:
+/// - `default = lit` — a string, bool, int or float literal
+/// - `default_json = "..."` — any JSON, checked at compile time
+/// - `default_fn = "path"` — a function returning the field's type
+///
+/// and otherwise from `Default::default()` for its type (`None` for `Option`).
+///
+/// Defaults are computed when they are needed, not stored as constants, so
+/// `default_fn` can depend on the environment.
+///
+/// `set_field` only stores values that deserialize into the field's type, and
+/// `load`/`get_field` fall back to the default (logging a warning) for stored
+/// values that no longer do.
+///
+/// Fields can also declare validators, checked by `set_field` and `save`
+/// before anything is stored:
+/// - `min = N`, `max = N` — numeric bounds (inclusive)
+/// - `one_of = [..]` — allowed values
+/// - `pattern = "regex"` — strings must match (unanchored)
+/// - `non_empty` — no empty string, array, object or `null`
+///
+/// On `Option` fields, `null` passes every validator except `non_empty`.
+///
+/// Renaming a field keeps its stored value with `rename_from = "old_name"`
+/// (repeatable). For other changes, declare `#[settings(version = N,
+/// upgrade = "path::to::fn")]` on the struct, where the function has the
+/// signature of `shipkit_core::settings::upgrade::UpgradeFn` and is called
+/// once per version step. `load`, `save` and `set_field` apply both before
+/// touching the namespace, write the result back, and record the namespace's
+/// version, so values they write are never upgraded a second time.
+///
+/// Storage keys follow serde: `#[serde(rename_all = "..")]` and
+/// `#[serde(rename = "..")]` apply, and `#[serde(skip)]` fields are not
+/// stored. Attributes that would make `save` and `load` disagree on keys
+/// (`flatten`, `skip_serializing_if`, one-sided renames or skips, custom
+/// `with` functions) are compile errors.
+///
+/// Each stored field also gets typed accessors on the struct, with the
+/// field's visibility: a `Field` constant (`THEME`), a getter (`theme`) and a
+/// setter (`set_theme`), all going through a `SettingsManager`. Fields whose
+/// accessors would shadow a `Settings` method (`load`, `schema`, or `field`
+/// for `set_field`) are compile errors; rename them in Rust and keep the
+/// stored key with `#[serde(rename = "..")]`.
+///
+/// `schema()` describes the struct as JSON Schema: field types, defaults,
+/// nullability, validator constraints, and doc comments as descriptions.
+///
+/// `typescript()` emits a TypeScript interface for the struct, plus
+/// `<NAME>_NAMESPACE` and `<NAME>_DEFAULTS` constants. `one_of` fields become
+/// literal unions.
 ///
 /// # Example
 /// ```ignore
@@ -35,7 +117,7 @@ struct SettingsReceiver {
 /// pub struct AppearanceSettings {
 ///     #[settings(default = "system")]
 ///     pub theme: String,
-///     #[settings(default = 1.0)]
+///     #[settings(default = 1.0, min = 0.5, max = 3.0)]
 ///     pub font_scale: f64,
 ///     #[settings(default = true)]
 ///     pub animations_enabled: bool,
@@ -67,39 +149,117 @@ pub fn derive_settings(input: TokenStream) -> TokenStream {
         }
     };
 
+    // Trait methods are called fully qualified, since the struct may have
+    // inherent methods of the same names
+    let settings = quote!(<Self as shipkit_core::settings::Settings>);
+
     let mut errors = Vec::new();
-    let mut valid_defaults = Vec::new();
+    let mut names = Vec::new();
+    let mut defaults = Vec::new();
+    let mut validations = Vec::new();
+    let mut getters = Vec::new();
+    let mut properties = Vec::new();
+    let mut ts_fields = Vec::new();
+    let mut renames = Vec::new();
+    let mut accessors = Vec::new();
+
+    let rename_all = match serde_attrs::container(&receiver.attrs) {
+        Ok(rule) => rule,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // Storage key of each stored field: its serialized name
+    let mut stored = Vec::new();
     for field in &fields.fields {
         // darling's `supports(struct_named)` guarantees named fields
         let Some(ident) = &field.ident else { continue };
-        let name = ident.to_string();
-        let default_json = match &field.default {
-            Some(syn::Lit::Str(s)) => {
-                let val = s.value();
-                Ok(format!(
-                    "\"{}\"",
-                    val.replace('\\', "\\\\").replace('"', "\\\"")
-                ))
-            }
-            Some(syn::Lit::Bool(b)) => Ok(b.value.to_string()),
-            Some(syn::Lit::Int(i)) => Ok(i.base10_digits().to_string()),
-            Some(syn::Lit::Float(fl)) => Ok(fl.base10_digits().to_string()),
-            Some(other) => Err(syn::Error::new_spanned(
-                other,
-                "unsupported default value type; use string, bool, int, or float",
-            )),
-            None => {
-                if is_option_type(&field.ty) {
-                    Ok("null".to_string())
-                } else {
-                    Ok(default_for_type(&field.ty))
-                }
+        let serde = match serde_attrs::field(&field.attrs) {
+            Ok(serde) => serde,
+            Err(e) => {
+                errors.push(e);
+                continue;
             }
         };
-        match default_json {
-            Ok(json) => valid_defaults.push((name, json)),
+        if serde.skip {
+            if has_settings_attrs(field) {
+                errors.push(syn::Error::new_spanned(
+                    ident,
+                    "#[settings] attributes have no effect on #[serde(skip)] fields, which are not stored",
+                ));
+            }
+            continue;
+        }
+        let name = serde.rename.unwrap_or_else(|| {
+            let unraw = ident.unraw().to_string();
+            rename_all.map_or_else(|| unraw.clone(), |rule| rule.apply(&unraw))
+        });
+        if stored.iter().any(|(_, _, other)| *other == name) {
+            errors.push(syn::Error::new_spanned(
+                ident,
+                format!("another field is also stored as \"{name}\""),
+            ));
+        }
+        stored.push((field, ident, name));
+    }
+
+    let field_names: Vec<&String> = stored.iter().map(|(_, _, name)| name).collect();
+    let mut old_names: Vec<&String> = Vec::new();
+    for (field, ident, name) in &stored {
+        let (field, name) = (*field, name.clone());
+        let ty = &field.ty;
+        let type_name = quote!(#ty).to_string().replace(' ', "");
+        for old in &field.rename_from {
+            if field_names.contains(&old) {
+                errors.push(syn::Error::new_spanned(
+                    ident,
+                    format!("rename_from = \"{old}\" is still the key of a field in this struct"),
+                ));
+            } else if old_names.contains(&old) {
+                errors.push(syn::Error::new_spanned(
+                    ident,
+                    format!("rename_from = \"{old}\" is claimed by another field"),
+                ));
+            }
+            old_names.push(old);
+        }
+        if !field.rename_from.is_empty() {
+            let olds = &field.rename_from;
+            renames.push(quote! { (#name, &[#(#olds),*] as &[&str]) });
+        }
+        match validate::field_checks(field, &name) {
+            Ok(checks) => validations.push(quote! {
+                #name => {
+                    shipkit_core::settings::validate::of_type::<#ty>(#name, value, #type_name)?;
+                    #checks
+                }
+            }),
             Err(e) => errors.push(e),
         }
+        match default_value(field) {
+            Ok(default) => defaults.push(quote! { #name => #default, }),
+            Err(e) => errors.push(e),
+        }
+        properties.push(schema::property(field, &name));
+        ts_fields.push((field, name.clone()));
+        getters.push(quote! {
+            #name => shipkit_core::settings::validate::stored_or_default::<#ty>(
+                #settings::namespace(),
+                #name,
+                stored,
+                || #settings::field_default(#name),
+            ),
+        });
+        match accessor(field, ident, &name) {
+            Ok(accessor) => accessors.push(accessor),
+            Err(e) => errors.push(e),
+        }
+        names.push(name);
+    }
+
+    if let (Some(upgrade), None) = (&receiver.upgrade, receiver.version) {
+        errors.push(syn::Error::new_spanned(
+            upgrade,
+            "`upgrade` requires `version = N` on the struct",
+        ));
     }
 
     if !errors.is_empty() {
@@ -110,12 +270,52 @@ pub fn derive_settings(input: TokenStream) -> TokenStream {
         return combined.to_compile_error().into();
     }
 
-    let default_entries: Vec<_> = valid_defaults
-        .iter()
-        .map(|(name, json)| {
-            quote! { (#name, #json) }
-        })
-        .collect();
+    let validate_body = if validations.is_empty() {
+        quote! { let _ = (field, value); }
+    } else {
+        quote! {
+            match field {
+                #(#validations)*
+                _ => {}
+            }
+        }
+    };
+
+    let title = struct_name.to_string();
+    let description = schema::doc_comment(&receiver.attrs).map(|description| {
+        quote! { schema.insert("description".into(), serde_json::json!(#description)); }
+    });
+
+    let typescript = typescript::definition(
+        &title,
+        &receiver.attrs,
+        namespace,
+        &ts_fields
+            .iter()
+            .map(|(field, name)| typescript::TsField { field, name })
+            .collect::<Vec<_>>(),
+    );
+
+    let version = receiver.version.unwrap_or(0);
+    let upgrade = if receiver.version.is_some() || !renames.is_empty() {
+        let upgrade_fn = match &receiver.upgrade {
+            Some(path) => quote! { Some(#path as shipkit_core::settings::upgrade::UpgradeFn) },
+            None => quote! { None },
+        };
+        quote! {
+            fn upgrade(store: &dyn shipkit_core::settings::SettingsBackend) -> shipkit_core::error::Result<()> {
+                shipkit_core::settings::upgrade::run(
+                    store,
+                    #settings::namespace(),
+                    #version,
+                    &[#(#renames),*],
+                    #upgrade_fn,
+                )
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     let expanded = quote! {
         impl #impl_generics shipkit_core::settings::Settings for #struct_name #ty_generics #where_clause {
@@ -123,32 +323,48 @@ pub fn derive_settings(input: TokenStream) -> TokenStream {
                 #namespace
             }
 
-            fn field_defaults() -> &'static [(&'static str, &'static str)] {
-                &[#(#default_entries),*]
+            fn fields() -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+
+            fn field_default(field: &str) -> shipkit_core::error::Result<serde_json::Value> {
+                match field {
+                    #(#defaults)*
+                    _ => Err(shipkit_core::error::ShipKitError::SettingNotFound {
+                        namespace: #settings::namespace().to_string(),
+                        key: field.to_string(),
+                    }),
+                }
+            }
+
+            fn version() -> u32 {
+                #version
             }
 
+            #upgrade
+
             fn load(store: &dyn shipkit_core::settings::SettingsBackend) -> shipkit_core::error::Result<Self> {
+                #settings::upgrade(store)?;
                 let mut map = serde_json::Map::new();
-                for (field, default_json) in Self::field_defaults() {
-                    let value = match store.get(Self::namespace(), field)? {
-                        Some(v) => v,
-                        None => serde_json::from_str(default_json)
-                            .map_err(|e| shipkit_core::error::ShipKitError::InvalidSetting {
-                                key: field.to_string(),
-                                reason: e.to_string(),
-                            })?,
-                    };
-                    map.insert(field.to_string(), value);
+                for field in #settings::fields() {
+                    map.insert(field.to_string(), #settings::get_field(store, field)?);
                 }
                 serde_json::from_value(serde_json::Value::Object(map))
                     .map_err(|e| shipkit_core::error::ShipKitError::Serialization(e))
             }
 
             fn save(&self, store: &dyn shipkit_core::settings::SettingsBackend) -> shipkit_core::error::Result<()> {
+                // Upgrade first so the values written below are not taken for
+                // an older version's on the next load
+                #settings::upgrade(store)?;
                 let value = serde_json::to_value(self)?;
                 if let serde_json::Value::Object(map) = value {
+                    // Validate everything first so an invalid field leaves the store untouched
+                    for (key, val) in &map {
+                        #settings::validate_field(key, val)?;
+                    }
                     for (key, val) in map {
-                        store.set(Self::namespace(), &key, val)?;
+                        store.set(#settings::namespace(), &key, val)?;
                     }
                 }
                 Ok(())
@@ -158,40 +374,141 @@ pub fn derive_settings(input: TokenStream) -> TokenStream {
                 store: &dyn shipkit_core::settings::SettingsBackend,
                 field: &str,
             ) -> shipkit_core::error::Result<serde_json::Value> {
-                match store.get(Self::namespace(), field)? {
-                    Some(v) => Ok(v),
-                    None => {
-                        Self::field_defaults()
-                            .iter()
-                            .find(|(name, _)| *name == field)
-                            .map(|(_, default_json)| serde_json::from_str(default_json))
-                            .transpose()
-                            .map_err(|e| shipkit_core::error::ShipKitError::Serialization(e))?
-                            .ok_or_else(|| shipkit_core::error::ShipKitError::SettingNotFound {
-                                namespace: Self::namespace().to_string(),
-                                key: field.to_string(),
-                            })
-                    }
+                let stored = store.get(#settings::namespace(), field)?;
+                match field {
+                    #(#getters)*
+                    _ => Err(shipkit_core::error::ShipKitError::SettingNotFound {
+                        namespace: #settings::namespace().to_string(),
+                        key: field.to_string(),
+                    }),
                 }
             }
 
+            fn schema() -> serde_json::Value {
+                let mut properties = serde_json::Map::new();
+                #(#properties)*
+
+                let mut schema = serde_json::Map::new();
+                schema.insert(
+                    "$schema".into(),
+                    serde_json::json!("https://json-schema.org/draft/2020-12/schema"),
+                );
+                schema.insert("title".into(), serde_json::json!(#title));
+                #description
+                schema.insert("type".into(), serde_json::json!("object"));
+                schema.insert("properties".into(), serde_json::Value::Object(properties));
+                schema.insert("additionalProperties".into(), serde_json::json!(false));
+                serde_json::Value::Object(schema)
+            }
+
+            fn typescript() -> String {
+                format!(
+                    "{}{}}};\n",
+                    #typescript,
+                    shipkit_core::settings::typescript::defaults::<Self>(),
+                )
+            }
+
+            fn validate_field(
+                field: &str,
+                value: &serde_json::Value,
+            ) -> shipkit_core::error::Result<()> {
+                #validate_body
+                Ok(())
+            }
+
             fn set_field(
                 store: &dyn shipkit_core::settings::SettingsBackend,
                 field: &str,
                 value: serde_json::Value,
             ) -> shipkit_core::error::Result<()> {
-                if !Self::field_defaults().iter().any(|(name, _)| *name == field) {
+                if !#settings::fields().contains(&field) {
                     return Err(shipkit_core::error::ShipKitError::SettingNotFound {
-                        namespace: Self::namespace().to_string(),
+                        namespace: #settings::namespace().to_string(),
                         key: field.to_string(),
                     });
                 }
-                store.set(Self::namespace(), field, value)
+                #settings::validate_field(field, &value)?;
+                #settings::upgrade(store)?;
+                store.set(#settings::namespace(), field, value)
             }
         }
     };
 
-    expanded.into()
+    quote! {
+        #expanded
+
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            #(#accessors)*
+        }
+    }
+    .into()
+}
+
+/// Methods of the `Settings` trait. An inherent accessor with one of these
+/// names would take precedence over the trait method in `Type::load(..)`.
+const TRAIT_METHODS: &[&str] = &[
+    "namespace",
+    "fields",
+    "field_default",
+    "version",
+    "upgrade",
+    "load",
+    "save",
+    "get_field",
+    "schema",
+    "typescript",
+    "validate_field",
+    "set_field",
+];
+
+/// Typed key constant, getter and setter for one stored field, with the
+/// field's visibility.
+fn accessor(
+    field: &SettingsFieldReceiver,
+    ident: &syn::Ident,
+    name: &str,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let vis = &field.vis;
+    let ty = &field.ty;
+    let unraw = ident.unraw();
+    let constant = format_ident!("{}", unraw.to_string().to_uppercase());
+    let setter = format_ident!("set_{}", unraw);
+    for method in [unraw.to_string(), setter.to_string()] {
+        if TRAIT_METHODS.contains(&method.as_str()) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "the generated accessor `{method}` would shadow `Settings::{method}`; \
+                     rename the field and keep its key with #[serde(rename = \"{name}\")]"
+                ),
+            ));
+        }
+    }
+    let constant_doc = format!("Typed key of the `{name}` setting.");
+    let getter_doc = format!("The stored `{name}` setting, or its default.");
+    let setter_doc =
+        format!("Validate and store the `{name}` setting, notifying the manager's subscribers.");
+    Ok(quote! {
+        #[doc = #constant_doc]
+        #vis const #constant: shipkit_core::settings::Field<Self, #ty> =
+            shipkit_core::settings::Field::new(#name);
+
+        #[doc = #getter_doc]
+        #vis fn #ident(
+            manager: &shipkit_core::settings::SettingsManager,
+        ) -> shipkit_core::error::Result<#ty> {
+            manager.get_typed(Self::#constant)
+        }
+
+        #[doc = #setter_doc]
+        #vis fn #setter(
+            manager: &shipkit_core::settings::SettingsManager,
+            value: #ty,
+        ) -> shipkit_core::error::Result<()> {
+            manager.set_typed(Self::#constant, value)
+        }
+    })
 }
 
 fn is_option_type(ty: &syn::Type) -> bool {
@@ -203,19 +520,49 @@ fn is_option_type(ty: &syn::Type) -> bool {
     false
 }
 
-fn default_for_type(ty: &syn::Type) -> String {
-    if let syn::Type::Path(type_path) = ty
-        && let Some(segment) = type_path.path.segments.last()
-    {
-        let ident = segment.ident.to_string();
-        return match ident.as_str() {
-            "String" => "\"\"".to_string(),
-            "bool" => "false".to_string(),
-            "f32" | "f64" => "0.0".to_string(),
-            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
-            | "u128" | "usize" => "0".to_string(),
-            _ => "null".to_string(),
-        };
+fn has_settings_attrs(field: &SettingsFieldReceiver) -> bool {
+    field.default.is_some()
+        || field.default_fn.is_some()
+        || field.default_json.is_some()
+        || field.min.is_some()
+        || field.max.is_some()
+        || field.one_of.is_some()
+        || field.pattern.is_some()
+        || field.non_empty
+        || !field.rename_from.is_empty()
+}
+
+/// Expression for the field's default as a `Result<serde_json::Value>`,
+/// evaluated each time the default is needed.
+fn default_value(field: &SettingsFieldReceiver) -> syn::Result<proc_macro2::TokenStream> {
+    let ty = &field.ty;
+    match (&field.default, &field.default_fn, &field.default_json) {
+        (Some(lit), None, None) => match lit {
+            syn::Lit::Str(_) | syn::Lit::Bool(_) | syn::Lit::Int(_) | syn::Lit::Float(_) => {
+                Ok(quote! { Ok(serde_json::json!(#lit)) })
+            }
+            other => Err(syn::Error::new_spanned(
+                other,
+                "unsupported default value type; use string, bool, int, or float, \
+                 or `default_json`/`default_fn`",
+            )),
+        },
+        (None, Some(path), None) => Ok(quote! { Ok(serde_json::to_value::<#ty>(#path())?) }),
+        (None, None, Some(json)) => {
+            if let Err(e) = serde_json::from_str::<serde_json::Value>(&json.value()) {
+                return Err(syn::Error::new_spanned(
+                    json,
+                    format!("default_json is not valid JSON: {e}"),
+                ));
+            }
+            Ok(quote! { Ok(serde_json::from_str::<serde_json::Value>(#json)?) })
+        }
+        (None, None, None) => Ok(quote! {
+            Ok(serde_json::to_value(<#ty as ::core::default::Default>::default())?)
+        }),
+        _ => Err(syn::Error::new_spanned(
+            &field.ident,
+            "use only one of `default`, `default_fn` and `default_json`",
+        )),
     }
-    "null".to_string()
 }
@@ -0,0 +1,193 @@
+//! Code generation for `Settings::schema()`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::SettingsFieldReceiver;
+
+/// JSON shape of a Rust field type, as far as it can be read from the syntax.
+pub(crate) struct JsonType {
+    /// `"string"`, `"integer"`, ...; `None` for types that could be anything.
+    pub(crate) kind: Option<&'static str>,
+    /// Element type of arrays.
+    pub(crate) items: Option<Box<JsonType>>,
+    /// Value type of maps.
+    pub(crate) values: Option<Box<JsonType>>,
+    /// `Option<T>`.
+    pub(crate) nullable: bool,
+    /// Unsigned integers, which get an implicit `minimum` of 0.
+    pub(crate) unsigned: bool,
+}
+
+impl JsonType {
+    fn any() -> Self {
+        Self::of(None)
+    }
+
+    fn of(kind: Option<&'static str>) -> Self {
+        Self {
+            kind,
+            items: None,
+            values: None,
+            nullable: false,
+            unsigned: false,
+        }
+    }
+
+    /// `"key": value` pairs for a `serde_json::json!` object.
+    fn pairs(&self) -> Vec<TokenStream> {
+        let mut pairs = Vec::new();
+        match (self.kind, self.nullable) {
+            (Some(kind), true) => pairs.push(quote! { "type": [#kind, "null"] }),
+            (Some(kind), false) => pairs.push(quote! { "type": #kind }),
+            (None, _) => {}
+        }
+        if let Some(items) = &self.items {
+            let items = items.pairs();
+            pairs.push(quote! { "items": { #(#items),* } });
+        }
+        if let Some(values) = &self.values {
+            let values = values.pairs();
+            pairs.push(quote! { "additionalProperties": { #(#values),* } });
+        }
+        if self.unsigned {
+            pairs.push(quote! { "minimum": 0 });
+        }
+        pairs
+    }
+}
+
+/// Map a field type to its JSON shape. Types the derive can't see through
+/// (enums, nested structs, type aliases) are left unconstrained.
+pub(crate) fn json_type(ty: &syn::Type) -> JsonType {
+    match ty {
+        syn::Type::Reference(r) => json_type(&r.elem),
+        syn::Type::Paren(p) => json_type(&p.elem),
+        syn::Type::Group(g) => json_type(&g.elem),
+        syn::Type::Array(a) => array(json_type(&a.elem)),
+        syn::Type::Slice(s) => array(json_type(&s.elem)),
+        syn::Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return JsonType::any();
+            };
+            let args: Vec<&syn::Type> = match &segment.arguments {
+                syn::PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        syn::GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            match (segment.ident.to_string().as_str(), args.as_slice()) {
+                ("Option", [inner]) => JsonType {
+                    nullable: true,
+                    ..json_type(inner)
+                },
+                ("Box" | "Rc" | "Arc" | "Cow", [inner]) => json_type(inner),
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [inner]) => array(json_type(inner)),
+                ("HashMap" | "BTreeMap", [_, value]) => JsonType {
+                    values: Some(Box::new(json_type(value))),
+                    ..JsonType::of(Some("object"))
+                },
+                ("String" | "str" | "char" | "PathBuf", []) => JsonType::of(Some("string")),
+                ("bool", []) => JsonType::of(Some("boolean")),
+                ("f32" | "f64", []) => JsonType::of(Some("number")),
+                ("i8" | "i16" | "i32" | "i64" | "i128" | "isize", []) => {
+                    JsonType::of(Some("integer"))
+                }
+                ("u8" | "u16" | "u32" | "u64" | "u128" | "usize", []) => JsonType {
+                    unsigned: true,
+                    ..JsonType::of(Some("integer"))
+                },
+                _ => JsonType::any(),
+            }
+        }
+        _ => JsonType::any(),
+    }
+}
+
+fn array(items: JsonType) -> JsonType {
+    JsonType {
+        items: Some(Box::new(items)),
+        ..JsonType::of(Some("array"))
+    }
+}
+
+/// Text of `///` comments, with wrapped lines joined and blank lines kept
+/// as paragraph breaks. `None` when there are none.
+pub(crate) fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(s),
+                        ..
+                    }),
+                ..
+            }) => Some(s.value().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let text = lines
+        .split(|line| line.is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Statement inserting the field's JSON Schema into `properties`.
+pub(crate) fn property(field: &SettingsFieldReceiver, name: &str) -> TokenStream {
+    let mut shape = json_type(&field.ty);
+    if field.non_empty {
+        shape.nullable = false;
+    }
+    if field.min.is_some() {
+        shape.unsigned = false;
+    }
+
+    let mut pairs = shape.pairs();
+    if let Some(description) = doc_comment(&field.attrs) {
+        pairs.push(quote! { "description": #description });
+    }
+    pairs.push(quote! {
+        "default": <Self as shipkit_core::settings::Settings>::field_default(#name).ok()
+    });
+    if let Some(min) = &field.min {
+        pairs.push(quote! { "minimum": #min });
+    }
+    if let Some(max) = &field.max {
+        pairs.push(quote! { "maximum": #max });
+    }
+    if let Some(one_of) = &field.one_of {
+        let mut allowed: Vec<TokenStream> = one_of.elems.iter().map(|e| quote!(#e)).collect();
+        // `null` passes `one_of` on nullable fields, and is their default
+        if shape.nullable {
+            allowed.push(quote!(null));
+        }
+        pairs.push(quote! { "enum": [#(#allowed),*] });
+    }
+    if let Some(pattern) = &field.pattern {
+        pairs.push(quote! { "pattern": #pattern });
+    }
+    if field.non_empty {
+        match shape.kind {
+            Some("string") => pairs.push(quote! { "minLength": 1 }),
+            Some("array") => pairs.push(quote! { "minItems": 1 }),
+            Some("object") => pairs.push(quote! { "minProperties": 1 }),
+            _ => {}
+        }
+    }
+
+    quote! {
+        properties.insert(#name.to_string(), serde_json::json!({ #(#pairs),* }));
+    }
+}
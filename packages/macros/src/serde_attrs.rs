@@ -0,0 +1,201 @@
+//! The `#[serde(...)]` attributes that decide what a settings struct stores.
+//!
+//! `save` writes whatever `serde_json::to_value` produces, while `load`,
+//! `get_field` and the validators look fields up by key, so the derive has to
+//! agree with serde on every key. Attributes that would make the two
+//! disagree, or that `load` can't reproduce one key at a time, are rejected.
+
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+
+/// `#[serde(rename_all = "...")]` conventions, applied to snake_case field
+/// names the way serde does.
+#[derive(Clone, Copy)]
+pub(crate) enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(lit: &syn::LitStr) -> syn::Result<Self> {
+        Ok(match lit.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            other => {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    format!("unknown rename_all rule \"{other}\""),
+                ));
+            }
+        })
+    }
+
+    pub(crate) fn apply(self, field: &str) -> String {
+        match self {
+            Self::Lower | Self::Snake => field.to_string(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal => pascal_case(field),
+            Self::Camel => {
+                let pascal = pascal_case(field);
+                let mut chars = pascal.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                    None => pascal,
+                }
+            }
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+fn pascal_case(field: &str) -> String {
+    let mut out = String::new();
+    let mut capitalize = true;
+    for c in field.chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            out.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `rename_all` from the struct's serde attributes.
+pub(crate) fn container(attrs: &[syn::Attribute]) -> syn::Result<Option<RenameRule>> {
+    let mut rename_all = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                let lit = same_both_ways(&meta, "rename_all")?;
+                rename_all = Some(RenameRule::parse(&lit)?);
+                return Ok(());
+            }
+            for unsupported in ["transparent", "tag", "from", "try_from", "into"] {
+                if meta.path.is_ident(unsupported) {
+                    return Err(meta.error(format!(
+                        "#[serde({unsupported})] changes the stored shape and is not supported by Settings"
+                    )));
+                }
+            }
+            skip_value(&meta)
+        })?;
+    }
+    Ok(rename_all)
+}
+
+/// Storage-relevant serde attributes of one field.
+#[derive(Default)]
+pub(crate) struct SerdeField {
+    pub(crate) rename: Option<String>,
+    /// Neither serialized nor deserialized, so never stored.
+    pub(crate) skip: bool,
+}
+
+pub(crate) fn field(attrs: &[syn::Attribute]) -> syn::Result<SerdeField> {
+    let mut out = SerdeField::default();
+    let mut skip_serializing = None;
+    let mut skip_deserializing = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            let path = &meta.path;
+            if path.is_ident("rename") {
+                out.rename = Some(same_both_ways(&meta, "rename")?.value());
+            } else if path.is_ident("skip") {
+                out.skip = true;
+            } else if path.is_ident("skip_serializing") {
+                skip_serializing = Some(path.span());
+            } else if path.is_ident("skip_deserializing") {
+                skip_deserializing = Some(path.span());
+            } else if path.is_ident("flatten") {
+                return Err(meta.error(
+                    "#[serde(flatten)] is not supported by Settings; \
+                     give the nested struct its own namespace instead",
+                ));
+            } else if path.is_ident("skip_serializing_if") {
+                return Err(meta.error(
+                    "#[serde(skip_serializing_if)] is not supported by Settings: \
+                     `save` would leave the old value in the store",
+                ));
+            } else if ["with", "serialize_with", "deserialize_with"]
+                .iter()
+                .any(|name| path.is_ident(name))
+            {
+                return Err(meta.error(
+                    "custom (de)serializers are not supported by Settings: \
+                     stored values are checked against the field's own type",
+                ));
+            } else {
+                return skip_value(&meta);
+            }
+            Ok(())
+        })?;
+    }
+
+    match (skip_serializing, skip_deserializing) {
+        (Some(_), Some(_)) => out.skip = true,
+        (Some(span), None) | (None, Some(span)) => {
+            return Err(syn::Error::new(
+                span,
+                "skip_serializing and skip_deserializing must be used together \
+                 (or as `skip`) on settings fields",
+            ));
+        }
+        (None, None) => {}
+    }
+    Ok(out)
+}
+
+/// The value of `name = "..."`, or of `name(serialize = "...",
+/// deserialize = "...")` when both halves agree.
+fn same_both_ways(meta: &ParseNestedMeta<'_>, name: &str) -> syn::Result<syn::LitStr> {
+    if meta.input.peek(syn::Token![=]) {
+        return meta.value()?.parse();
+    }
+    let mut serialize: Option<syn::LitStr> = None;
+    let mut deserialize: Option<syn::LitStr> = None;
+    meta.parse_nested_meta(|inner| {
+        if inner.path.is_ident("serialize") {
+            serialize = Some(inner.value()?.parse()?);
+        } else if inner.path.is_ident("deserialize") {
+            deserialize = Some(inner.value()?.parse()?);
+        } else {
+            return Err(inner.error("expected `serialize` or `deserialize`"));
+        }
+        Ok(())
+    })?;
+    match (serialize, deserialize) {
+        (Some(ser), Some(de)) if ser.value() == de.value() => Ok(ser),
+        _ => Err(meta.error(format!(
+            "{name} must be the same for serialize and deserialize on settings types"
+        ))),
+    }
+}
+
+/// Consume the value of an attribute the derive doesn't care about.
+fn skip_value(meta: &ParseNestedMeta<'_>) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<proc_macro2::TokenStream>()?;
+    }
+    Ok(())
+}
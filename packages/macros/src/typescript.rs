@@ -0,0 +1,153 @@
+//! Code generation for `Settings::typescript()`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::SettingsFieldReceiver;
+use crate::schema::{JsonType, doc_comment, json_type};
+
+/// One field of the generated interface.
+pub(crate) struct TsField<'a> {
+    pub(crate) field: &'a SettingsFieldReceiver,
+    pub(crate) name: &'a str,
+}
+
+/// Expression building the interface and namespace constant for one struct,
+/// followed by the opening of its defaults object. The generated
+/// `typescript()` appends the default values, which are only known at
+/// runtime, and the closing `};`.
+///
+/// Property names and the constant prefix come from
+/// `shipkit_core::settings::typescript`, so they always match what the
+/// trait's default `typescript()` and `defaults` produce.
+pub(crate) fn definition(
+    struct_name: &str,
+    struct_attrs: &[syn::Attribute],
+    namespace: &str,
+    fields: &[TsField<'_>],
+) -> TokenStream {
+    let struct_comment = comment(struct_attrs, "");
+    let namespace = string_literal(namespace);
+    let fields = fields.iter().map(|f| {
+        let comment = comment(&f.field.attrs, "  ");
+        let name = f.name;
+        let ty = ts_type(f.field);
+        quote! {
+            out.push_str(#comment);
+            out.push_str(&format!(
+                "  {}: {};\n",
+                shipkit_core::settings::typescript::property_name(#name),
+                #ty,
+            ));
+        }
+    });
+
+    quote! {{
+        let constant = shipkit_core::settings::typescript::constant_prefix(#struct_name);
+        let mut out = String::new();
+        out.push_str(#struct_comment);
+        out.push_str(&format!("export interface {} {{\n", #struct_name));
+        #(#fields)*
+        out.push_str("}\n\n");
+        out.push_str(&format!("export const {constant}_NAMESPACE = {};\n\n", #namespace));
+        out.push_str(&format!("export const {constant}_DEFAULTS: {} = {{\n", #struct_name));
+        out
+    }}
+}
+
+/// TypeScript type of a field: a literal union for `one_of`, otherwise the
+/// type mapped from its JSON shape.
+fn ts_type(field: &SettingsFieldReceiver) -> String {
+    let shape = json_type(&field.ty);
+    let literals: Option<Vec<String>> = field.one_of.as_ref().and_then(|one_of| {
+        one_of
+            .elems
+            .iter()
+            .map(|elem| match elem {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => Some(string_literal(&s.value())),
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(i),
+                    ..
+                }) => Some(i.base10_digits().to_string()),
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Bool(b),
+                    ..
+                }) => Some(b.value.to_string()),
+                _ => None,
+            })
+            .collect()
+    });
+
+    let base = match literals {
+        Some(literals) => literals.join(" | "),
+        None => non_null(&shape),
+    };
+    if shape.nullable && !field.non_empty {
+        format!("{base} | null")
+    } else {
+        base
+    }
+}
+
+fn non_null(shape: &JsonType) -> String {
+    match shape.kind {
+        Some("string") => "string".into(),
+        Some("integer" | "number") => "number".into(),
+        Some("boolean") => "boolean".into(),
+        Some("array") => match &shape.items {
+            Some(items) => format!("{}[]", element(items)),
+            None => "unknown[]".into(),
+        },
+        Some("object") => match &shape.values {
+            Some(values) => format!("Record<string, {}>", with_null(values)),
+            None => "Record<string, unknown>".into(),
+        },
+        _ => "unknown".into(),
+    }
+}
+
+fn with_null(shape: &JsonType) -> String {
+    if shape.nullable && shape.kind.is_some() {
+        format!("{} | null", non_null(shape))
+    } else {
+        non_null(shape)
+    }
+}
+
+/// Array element type, parenthesized when it is a union.
+fn element(shape: &JsonType) -> String {
+    let ty = with_null(shape);
+    if ty.contains(" | ") {
+        format!("({ty})")
+    } else {
+        ty
+    }
+}
+
+/// `/** ... */` from doc comments, or nothing.
+fn comment(attrs: &[syn::Attribute], indent: &str) -> String {
+    let Some(text) = doc_comment(attrs) else {
+        return String::new();
+    };
+    let text = text.replace("*/", "*\\/");
+    if !text.contains('\n') {
+        return format!("{indent}/** {text} */\n");
+    }
+    let mut out = format!("{indent}/**\n");
+    for line in text.lines() {
+        if line.is_empty() {
+            out.push_str(&format!("{indent} *\n"));
+        } else {
+            out.push_str(&format!("{indent} * {line}\n"));
+        }
+    }
+    out.push_str(&format!("{indent} */\n"));
+    out
+}
+
+fn string_literal(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
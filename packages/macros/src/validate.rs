@@ -0,0 +1,106 @@
+//! Code generation for the `min`, `max`, `one_of`, `pattern` and
+//! `non_empty` field attributes.
+
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+
+use crate::{SettingsFieldReceiver, is_option_type};
+
+/// Statements that check `value` against the field's validators, returning
+/// early with `InvalidSetting`. Empty when the field has none.
+///
+/// `Option` fields accept `null` for every rule except `non_empty`.
+pub(crate) fn field_checks(field: &SettingsFieldReceiver, name: &str) -> syn::Result<TokenStream> {
+    let min = field.min.as_ref().map(number).transpose()?;
+    let max = field.max.as_ref().map(number).transpose()?;
+    if let (Some((min, _)), Some((max, lit))) = (&min, &max)
+        && min > max
+    {
+        return Err(syn::Error::new_spanned(
+            lit,
+            "max must not be smaller than min",
+        ));
+    }
+    if let Some(pattern) = &field.pattern
+        && let Err(e) = regex::Regex::new(&pattern.value())
+    {
+        return Err(syn::Error::new_spanned(
+            pattern,
+            format!("invalid pattern: {e}"),
+        ));
+    }
+
+    let mut checks = Vec::new();
+    if let Some((min, _)) = min {
+        let min = Literal::f64_unsuffixed(min);
+        checks.push(quote! { shipkit_core::settings::validate::min(#name, value, #min)?; });
+    }
+    if let Some((max, _)) = max {
+        let max = Literal::f64_unsuffixed(max);
+        checks.push(quote! { shipkit_core::settings::validate::max(#name, value, #max)?; });
+    }
+    if let Some(one_of) = &field.one_of {
+        if one_of.elems.is_empty() {
+            return Err(syn::Error::new_spanned(
+                one_of,
+                "one_of needs at least one value",
+            ));
+        }
+        let allowed = one_of.elems.iter();
+        checks.push(quote! {
+            shipkit_core::settings::validate::one_of(
+                #name,
+                value,
+                &[#(serde_json::json!(#allowed)),*],
+            )?;
+        });
+    }
+    if let Some(pattern) = &field.pattern {
+        checks.push(quote! {
+            static PATTERN: shipkit_core::settings::validate::Pattern =
+                shipkit_core::settings::validate::Pattern::new(#pattern);
+            shipkit_core::settings::validate::pattern(#name, value, &PATTERN)?;
+        });
+    }
+
+    let checks = if checks.is_empty() || !is_option_type(&field.ty) {
+        quote! { #(#checks)* }
+    } else {
+        quote! {
+            if !value.is_null() {
+                #(#checks)*
+            }
+        }
+    };
+    let non_empty = field
+        .non_empty
+        .then(|| quote! { shipkit_core::settings::validate::non_empty(#name, value)?; });
+
+    Ok(quote! { #non_empty #checks })
+}
+
+/// Numeric value of a `min`/`max` literal, which may be negated.
+fn number(expr: &syn::Expr) -> syn::Result<(f64, &syn::Expr)> {
+    let value = match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(i),
+            ..
+        }) => i.base10_parse::<f64>()?,
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Float(f),
+            ..
+        }) => f.base10_parse::<f64>()?,
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr: inner,
+            ..
+        }) => -number(inner)?.0,
+        other => {
+            return Err(syn::Error::new_spanned(
+                other,
+                "min and max must be integer or float literals",
+            ));
+        }
+    };
+    Ok((value, expr))
+}
@@ -0,0 +1,7 @@
+//! Attribute errors reported when `#[derive(Settings)]` expands.
+
+#[test]
+fn rejected_attributes() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
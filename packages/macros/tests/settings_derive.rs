@@ -0,0 +1,549 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use shipkit_core::error::ShipKitError;
+use shipkit_core::settings::{Settings, SettingsBackend};
+use shipkit_core::{ConnectionPool, SqliteSettingsStore};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "appearance")]
+struct Appearance {
+    #[settings(default = "system", one_of = ["light", "dark", "system"])]
+    theme: String,
+    #[settings(default = 1.0, min = 0.5, max = 3.0)]
+    font_scale: f64,
+    #[settings(default = 0, min = -10)]
+    letter_spacing: i64,
+    #[settings(default = "#3b82f6", pattern = "^#[0-9a-f]{6}$")]
+    accent: String,
+    #[settings(default = "ShipKit", non_empty)]
+    title: String,
+    #[settings(min = 1)]
+    max_tabs: Option<u32>,
+}
+
+fn store() -> SqliteSettingsStore {
+    let pool = ConnectionPool::in_memory().expect("pool");
+    SqliteSettingsStore::new(pool).expect("store")
+}
+
+fn invalid_reason(result: shipkit_core::Result<()>) -> String {
+    match result {
+        Err(ShipKitError::InvalidSetting { key, reason }) => format!("{key}: {reason}"),
+        other => format!("unexpected: {other:?}"),
+    }
+}
+
+#[test]
+fn set_field_rejects_values_that_fail_validators() {
+    let store = store();
+
+    assert_eq!(
+        invalid_reason(Appearance::set_field(&store, "font_scale", json!(5))),
+        "font_scale: must be at most 3, got 5"
+    );
+    assert_eq!(
+        invalid_reason(Appearance::set_field(&store, "letter_spacing", json!(-11))),
+        "letter_spacing: must be at least -10, got -11"
+    );
+    assert_eq!(
+        invalid_reason(Appearance::set_field(&store, "theme", json!("neon"))),
+        "theme: must be one of [\"light\", \"dark\", \"system\"], got \"neon\""
+    );
+    assert_eq!(
+        invalid_reason(Appearance::set_field(&store, "accent", json!("blue"))),
+        "accent: must match ^#[0-9a-f]{6}$, got \"blue\""
+    );
+    assert_eq!(
+        invalid_reason(Appearance::set_field(&store, "title", json!(""))),
+        "title: must not be empty, got \"\""
+    );
+    assert_eq!(store.get_all("appearance").expect("get_all").len(), 0);
+
+    Appearance::set_field(&store, "font_scale", json!(2.5)).expect("valid value");
+    Appearance::set_field(&store, "max_tabs", json!(null)).expect("null option");
+    assert!(Appearance::set_field(&store, "max_tabs", json!(0)).is_err());
+}
+
+#[test]
+fn save_validates_every_field_before_writing() {
+    let store = store();
+    let mut settings = Appearance::load(&store).expect("defaults");
+    settings.theme = "dark".into();
+    settings.font_scale = 0.1;
+
+    assert_eq!(
+        invalid_reason(settings.save(&store)),
+        "font_scale: must be at least 0.5, got 0.1"
+    );
+    assert_eq!(store.get("appearance", "theme").expect("get"), None);
+
+    settings.font_scale = 1.5;
+    settings.save(&store).expect("save");
+    assert_eq!(Appearance::load(&store).expect("load"), settings);
+}
+
+#[test]
+fn validate_field_accepts_fields_without_validators() {
+    assert!(Appearance::validate_field("font_scale", &json!(1)).is_ok());
+    assert!(Appearance::validate_field("unknown", &json!("anything")).is_ok());
+}
+
+#[test]
+fn set_field_rejects_values_of_the_wrong_type() {
+    let store = store();
+
+    assert_eq!(
+        invalid_reason(Appearance::set_field(&store, "font_scale", json!("big"))),
+        "font_scale: \"big\" is not a valid f64: invalid type: string \"big\", expected f64"
+    );
+    assert!(Appearance::set_field(&store, "max_tabs", json!(-1)).is_err());
+    assert_eq!(store.get_all("appearance").expect("get_all").len(), 0);
+}
+
+#[test]
+fn load_falls_back_to_defaults_for_values_that_no_longer_fit() {
+    let store = store();
+    // Written behind the derive's back, e.g. by an older build with other types
+    store
+        .set("appearance", "font_scale", json!("big"))
+        .expect("set");
+    store
+        .set("appearance", "theme", json!("dark"))
+        .expect("set");
+
+    let settings = Appearance::load(&store).expect("load");
+    assert_eq!(settings.font_scale, 1.0);
+    assert_eq!(settings.theme, "dark");
+    assert_eq!(
+        Appearance::get_field(&store, "font_scale").expect("get_field"),
+        json!(1.0)
+    );
+}
+
+/// Editor preferences.
+///
+/// Shown on the Editor tab.
+#[derive(Debug, Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "editor")]
+struct Editor {
+    /// Spaces per indentation level.
+    #[settings(default = 4, min = 1, max = 8)]
+    tab_width: u8,
+    /// Ruler columns, left to right.
+    rulers: Vec<u16>,
+    font_family: Option<String>,
+    #[settings(default = "utf-8", non_empty)]
+    encoding: String,
+    #[settings(default = 120)]
+    wrap_column: u32,
+    keymap: std::collections::HashMap<String, String>,
+}
+
+#[test]
+fn schema_describes_types_defaults_and_constraints() {
+    let schema = Editor::schema();
+
+    assert_eq!(schema["title"], json!("Editor"));
+    assert_eq!(
+        schema["description"],
+        json!("Editor preferences.\n\nShown on the Editor tab.")
+    );
+    assert_eq!(schema["type"], json!("object"));
+    assert_eq!(schema["additionalProperties"], json!(false));
+
+    let properties = &schema["properties"];
+    assert_eq!(
+        properties["tab_width"],
+        json!({
+            "type": "integer",
+            "description": "Spaces per indentation level.",
+            "default": 4,
+            "minimum": 1,
+            "maximum": 8,
+        })
+    );
+    assert_eq!(properties["rulers"]["type"], json!("array"));
+    assert_eq!(
+        properties["rulers"]["items"],
+        json!({ "type": "integer", "minimum": 0 })
+    );
+    assert_eq!(
+        properties["rulers"]["description"],
+        json!("Ruler columns, left to right.")
+    );
+    assert_eq!(properties["font_family"]["type"], json!(["string", "null"]));
+    assert_eq!(properties["encoding"]["minLength"], json!(1));
+    assert_eq!(properties["wrap_column"]["minimum"], json!(0));
+    assert_eq!(
+        properties["keymap"]["additionalProperties"],
+        json!({ "type": "string" })
+    );
+}
+
+#[test]
+fn schema_lists_allowed_values_and_patterns() {
+    let properties = Appearance::schema()["properties"].clone();
+
+    assert_eq!(
+        properties["theme"]["enum"],
+        json!(["light", "dark", "system"])
+    );
+    assert_eq!(properties["accent"]["pattern"], json!("^#[0-9a-f]{6}$"));
+    assert_eq!(properties["max_tabs"]["type"], json!(["integer", "null"]));
+    assert_eq!(properties["max_tabs"]["minimum"], json!(1));
+    assert_eq!(properties["letter_spacing"]["minimum"], json!(-10));
+}
+
+#[derive(Debug, Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "sync")]
+struct Sync {
+    #[settings(one_of = ["hourly", "daily"])]
+    interval: Option<String>,
+}
+
+#[test]
+fn schema_allows_null_among_the_values_of_nullable_fields() {
+    let interval = &Sync::schema()["properties"]["interval"];
+
+    assert_eq!(interval["type"], json!(["string", "null"]));
+    assert_eq!(interval["default"], json!(null));
+    assert_eq!(interval["enum"], json!(["hourly", "daily", null]));
+    Sync::validate_field("interval", &json!(null)).expect("null is allowed");
+}
+
+#[test]
+fn typescript_declares_interface_namespace_and_defaults() {
+    assert_eq!(
+        Appearance::typescript(),
+        r##"export interface Appearance {
+  theme: "light" | "dark" | "system";
+  font_scale: number;
+  letter_spacing: number;
+  accent: string;
+  title: string;
+  max_tabs: number | null;
+}
+
+export const APPEARANCE_NAMESPACE = "appearance";
+
+export const APPEARANCE_DEFAULTS: Appearance = {
+  theme: "system",
+  font_scale: 1.0,
+  letter_spacing: 0,
+  accent: "#3b82f6",
+  title: "ShipKit",
+  max_tabs: null,
+};
+"##
+    );
+}
+
+#[test]
+fn typescript_maps_collections_and_doc_comments() {
+    let typescript = Editor::typescript();
+
+    assert!(typescript.starts_with(
+        "/**\n * Editor preferences.\n *\n * Shown on the Editor tab.\n */\nexport interface Editor {\n"
+    ));
+    assert!(typescript.contains("  /** Spaces per indentation level. */\n  tab_width: number;\n"));
+    assert!(typescript.contains("  rulers: number[];\n"));
+    assert!(typescript.contains("  font_family: string | null;\n"));
+    assert!(typescript.contains("  keymap: Record<string, string>;\n"));
+    assert!(typescript.contains("export const EDITOR_NAMESPACE = \"editor\";\n"));
+}
+
+/// v1 stored `tab_size`; v2 renamed it to `indent` and stored widths in
+/// columns rather than tabs of 8.
+#[derive(Debug, PartialEq, Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "layout", version = 2, upgrade = "upgrade_layout")]
+struct Layout {
+    #[settings(default = 4, rename_from = "tab_size", rename_from = "tabs")]
+    indent: u32,
+    #[settings(default = 80)]
+    width: u32,
+}
+
+fn upgrade_layout(
+    from: u32,
+    values: &mut serde_json::Map<String, serde_json::Value>,
+) -> shipkit_core::Result<()> {
+    if from == 1
+        && let Some(width) = values.get("width").and_then(serde_json::Value::as_u64)
+    {
+        values.insert("width".into(), json!(width * 8));
+    }
+    Ok(())
+}
+
+#[test]
+fn load_renames_fields_and_runs_upgrades() {
+    use shipkit_core::settings::upgrade::stored_version;
+
+    let store = store();
+    store
+        .set("_shipkit.versions", "layout", json!(1))
+        .expect("set");
+    store.set("layout", "tab_size", json!(2)).expect("set");
+    store.set("layout", "width", json!(10)).expect("set");
+
+    let layout = Layout::load(&store).expect("load");
+    assert_eq!(
+        layout,
+        Layout {
+            indent: 2,
+            width: 80
+        }
+    );
+    assert_eq!(Layout::version(), 2);
+    assert_eq!(stored_version(&store, "layout").expect("version"), Some(2));
+    assert_eq!(store.get("layout", "tab_size").expect("get"), None);
+    assert_eq!(store.get("layout", "indent").expect("get"), Some(json!(2)));
+
+    // Loading again leaves the upgraded values alone
+    assert_eq!(Layout::load(&store).expect("reload"), layout);
+}
+
+#[test]
+fn structs_without_versions_do_not_record_one() {
+    use shipkit_core::settings::upgrade::stored_version;
+
+    let store = store();
+    Appearance::load(&store).expect("load");
+    assert_eq!(Appearance::version(), 0);
+    assert_eq!(stored_version(&store, "appearance").expect("version"), None);
+}
+
+#[test]
+fn save_and_set_field_record_the_version_they_write() {
+    use shipkit_core::settings::upgrade::stored_version;
+
+    let layout = Layout {
+        indent: 4,
+        width: 100,
+    };
+    let saved = store();
+    layout.save(&saved).expect("save");
+    assert_eq!(stored_version(&saved, "layout").expect("version"), Some(2));
+    assert_eq!(Layout::load(&saved).expect("load"), layout);
+
+    let set = store();
+    Layout::set_field(&set, "width", json!(100)).expect("set_field");
+    assert_eq!(Layout::load(&set).expect("load").width, 100);
+
+    // Values from an older version are upgraded before the write lands
+    let store = store();
+    store
+        .set("_shipkit.versions", "layout", json!(1))
+        .expect("set");
+    store.set("layout", "tab_size", json!(2)).expect("set");
+    store.set("layout", "width", json!(10)).expect("set");
+    Layout::set_field(&store, "width", json!(100)).expect("set_field");
+    assert_eq!(
+        Layout::load(&store).expect("load"),
+        Layout {
+            indent: 2,
+            width: 100
+        }
+    );
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Wrap {
+    #[default]
+    Off,
+    Word,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Margins {
+    top: u32,
+    bottom: u32,
+}
+
+fn default_margins() -> Margins {
+    Margins { top: 8, bottom: 16 }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "document")]
+struct Document {
+    #[settings(default_json = r#"["rust", "toml"]"#)]
+    languages: Vec<String>,
+    #[settings(default_fn = "default_margins")]
+    margins: Margins,
+    wrap: Wrap,
+    #[settings(default_json = r#"{"ctrl+s": "save"}"#)]
+    shortcuts: std::collections::BTreeMap<String, String>,
+    history: Vec<String>,
+}
+
+#[test]
+fn defaults_cover_collections_enums_and_nested_structs() {
+    let document = Document::load(&store()).expect("load");
+
+    assert_eq!(
+        document,
+        Document {
+            languages: vec!["rust".into(), "toml".into()],
+            margins: Margins { top: 8, bottom: 16 },
+            wrap: Wrap::Off,
+            shortcuts: [("ctrl+s".to_string(), "save".to_string())].into(),
+            history: Vec::new(),
+        }
+    );
+    assert_eq!(
+        Document::field_default("margins").expect("margins"),
+        json!({ "top": 8, "bottom": 16 })
+    );
+    assert_eq!(Document::field_default("wrap").expect("wrap"), json!("off"));
+    assert!(matches!(
+        Document::field_default("missing"),
+        Err(ShipKitError::SettingNotFound { .. })
+    ));
+
+    let schema = Document::schema();
+    assert_eq!(
+        schema["properties"]["languages"]["default"],
+        json!(["rust", "toml"])
+    );
+    assert_eq!(schema["properties"]["history"]["default"], json!([]));
+    assert_eq!(
+        Editor::schema()["properties"]["rulers"]["default"],
+        json!([])
+    );
+
+    let typescript = Document::typescript();
+    assert!(typescript.ends_with(
+        "  languages: [\"rust\",\"toml\"],\n  margins: {\"bottom\":16,\"top\":8},\n  wrap: \"off\",\n  shortcuts: {\"ctrl+s\":\"save\"},\n  history: [],\n};\n"
+    ));
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, shipkit_core::Settings)]
+#[serde(rename_all = "camelCase")]
+#[settings(namespace = "window")]
+struct Window {
+    #[settings(default = true)]
+    restore_position: bool,
+    #[serde(rename = "zoom-level")]
+    #[settings(default = 1.0, min = 0.25)]
+    zoom: f64,
+    #[settings(default = "dark", rename_from = "theme_name")]
+    r#type: String,
+    #[serde(skip)]
+    focused: bool,
+}
+
+#[test]
+fn storage_keys_follow_serde_renames() {
+    let store = store();
+    assert_eq!(Window::fields(), ["restorePosition", "zoom-level", "type"]);
+
+    let window = Window {
+        restore_position: false,
+        zoom: 2.0,
+        r#type: "light".into(),
+        focused: true,
+    };
+    window.save(&store).expect("save");
+    let stored = store.get_all("window").expect("get_all");
+    assert_eq!(stored.len(), 3);
+    assert_eq!(stored["zoom-level"], json!(2.0));
+
+    let loaded = Window::load(&store).expect("load");
+    assert_eq!(
+        loaded,
+        Window {
+            focused: false,
+            ..window
+        }
+    );
+
+    assert_eq!(
+        invalid_reason(Window::set_field(&store, "zoom-level", json!(0.1))),
+        "zoom-level: must be at least 0.25, got 0.1"
+    );
+    assert!(matches!(
+        Window::set_field(&store, "zoom", json!(1.0)),
+        Err(ShipKitError::SettingNotFound { .. })
+    ));
+
+    let typescript = Window::typescript();
+    assert!(typescript.contains("  restorePosition: boolean;\n  \"zoom-level\": number;\n"));
+    assert!(typescript.contains("  \"zoom-level\": 1.0,\n"));
+    assert!(!typescript.contains("focused"));
+}
+
+#[test]
+fn typed_accessors_read_and_write_fields() {
+    use shipkit_core::SettingsManager;
+    use shipkit_core::settings::Field;
+
+    let pool = ConnectionPool::in_memory().expect("pool");
+    let manager = SettingsManager::new(SqliteSettingsStore::new(pool).expect("store"));
+
+    assert_eq!(Appearance::theme(&manager).expect("default"), "system");
+    Appearance::set_theme(&manager, "dark".into()).expect("set");
+    assert_eq!(Appearance::theme(&manager).expect("get"), "dark");
+
+    Appearance::set_max_tabs(&manager, Some(3)).expect("set");
+    assert_eq!(Appearance::max_tabs(&manager).expect("get"), Some(3));
+    assert_eq!(
+        invalid_reason(Appearance::set_font_scale(&manager, 9.0)),
+        "font_scale: must be at most 3, got 9.0"
+    );
+
+    let zoom: Field<Window, f64> = Window::ZOOM;
+    assert_eq!(zoom.key(), "zoom-level");
+    assert_eq!(Window::r#type(&manager).expect("default"), "dark");
+    Window::set_type(&manager, "light".into()).expect("set");
+    assert_eq!(manager.get_typed(Window::TYPE).expect("get"), "light");
+}
+
+#[test]
+fn raw_writes_to_registered_namespaces_are_validated() {
+    use shipkit_core::SettingsManager;
+
+    let pool = ConnectionPool::in_memory().expect("pool");
+    let mut manager = SettingsManager::new(SqliteSettingsStore::new(pool).expect("store"));
+    manager.register::<Appearance>();
+
+    assert_eq!(
+        invalid_reason(manager.set_value("appearance", "font_scale", json!(9.0))),
+        "font_scale: must be at most 3, got 9.0"
+    );
+    assert!(
+        manager
+            .set_value("appearance", "font_scale", json!("big"))
+            .is_err()
+    );
+    assert_eq!(
+        manager.get_value("appearance", "font_scale").expect("get"),
+        None
+    );
+    manager
+        .set_value("appearance", "font_scale", json!(2.0))
+        .expect("in range");
+}
+
+#[test]
+fn manager_upgrades_a_namespace_before_its_first_read() {
+    use shipkit_core::SettingsManager;
+
+    let pool = ConnectionPool::in_memory().expect("pool");
+    let store = SqliteSettingsStore::new(pool.clone()).expect("store");
+    store.set("layout", "tab_size", json!(2)).expect("set");
+    store.set("window", "theme_name", json!("light")).expect("set");
+
+    let mut manager = SettingsManager::new(SqliteSettingsStore::new(pool).expect("store"));
+    assert_eq!(Layout::indent(&manager).expect("accessor"), 2);
+    assert_eq!(manager.get_typed(Window::TYPE).expect("typed"), "light");
+
+    manager.register::<Layout>();
+    store.set("layout", "tabs", json!(3)).expect("set");
+    // Already upgraded this session, so the stray old key is left alone
+    assert_eq!(
+        manager.get_value("layout", "indent").expect("raw"),
+        Some(json!(2))
+    );
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "startup")]
+struct Startup {
+    load: bool,
+    #[settings(default = 1)]
+    version: u32,
+    field: String,
+    #[serde(rename = "schema")]
+    schema_url: String,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[serde(rename_all = "camelCase")]
+#[settings(namespace = "window")]
+struct Window {
+    font_size: u32,
+    #[serde(rename = "fontSize")]
+    legacy_font_size: u32,
+}
+
+fn main() {}
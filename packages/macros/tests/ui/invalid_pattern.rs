@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "profile")]
+struct Profile {
+    #[settings(default = "shipkit", pattern = "^[a-z+$")]
+    handle: String,
+}
+
+fn main() {}
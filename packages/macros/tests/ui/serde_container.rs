@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[serde(transparent)]
+#[settings(namespace = "zoom")]
+struct Zoom {
+    level: f64,
+}
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[serde(tag = "kind")]
+#[settings(namespace = "sidebar")]
+struct Sidebar {
+    width: u32,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Margins {
+    top: u32,
+    bottom: u32,
+}
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "page")]
+struct Page {
+    #[serde(flatten)]
+    margins: Margins,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "profile")]
+struct Profile {
+    #[serde(rename(serialize = "displayName", deserialize = "display_name"))]
+    display_name: String,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "session")]
+struct Session {
+    #[serde(skip_serializing)]
+    token: String,
+}
+
+fn main() {}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize, Serializer};
+
+fn as_upper<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_uppercase())
+}
+
+mod lowercase {
+    use serde::Deserialize;
+
+    pub fn serialize<S: serde::Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_lowercase())
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<String, D::Error> {
+        String::deserialize(deserializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, shipkit_core::Settings)]
+#[settings(namespace = "profile")]
+struct Profile {
+    #[serde(with = "lowercase")]
+    handle: String,
+    #[serde(serialize_with = "as_upper")]
+    initials: String,
+}
+
+fn main() {}